@@ -28,6 +28,9 @@ class BlauState:
     def to_json(self) -> str:
         ...
 
+    def render(self, ansi: bool = False) -> str:
+        ...
+
     def players(self) -> List[Tuple[str, int]]:
         ...
 
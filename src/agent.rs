@@ -1,7 +1,11 @@
 use crate::game_state::GameState;
 use crate::player_move::Move;
 use crate::player_state::{PlayerState, played_column};
+use crate::rules::{Handicap, RuleSet};
 use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::sync::Mutex;
 
 pub fn create_agent(difficulty: usize) -> Box<dyn Agent + Send> {
     match difficulty {
@@ -11,16 +15,70 @@ pub fn create_agent(difficulty: usize) -> Box<dyn Agent + Send> {
     }
 }
 
+/// Creates an agent that plays below its level, as a handicap. Its blunders
+/// are drawn from `seed`, so that games can be replayed.
+pub fn create_handicapped_agent(
+    difficulty: usize,
+    handicap: &Handicap,
+    seed: u64,
+) -> Box<dyn Agent + Send> {
+    let agent: Box<dyn Agent + Send> = match handicap.search_budget {
+        Some(budget) if difficulty > 0 => {
+            Box::new(RoundPlanningAgent::with_budget(difficulty > 1, budget))
+        }
+        _ => create_agent(difficulty),
+    };
+    if handicap.blunder_percent == 0 {
+        return agent;
+    }
+    Box::new(BlunderingAgent {
+        agent,
+        blunder_percent: handicap.blunder_percent,
+        rng: Mutex::new(ChaCha8Rng::seed_from_u64(seed)),
+    })
+}
+
 pub trait Agent {
     fn choose_action(&self, game: &GameState) -> Move;
+
+    /// Picks the wall column for a completed row in free-placement games.
+    /// By default, this is the column that scores the most right away.
+    fn choose_placement(
+        &self,
+        game: &GameState,
+        player_idx: usize,
+        row: usize,
+    ) -> Option<usize> {
+        game.players[player_idx].best_placement(row)
+    }
+}
+
+/// Picks the move that sends the fewest tiles to the floor, as a safe
+/// default for players who can't or won't choose.
+pub fn smallest_floor_move(game: &GameState) -> Option<Move> {
+    game.valid_moves()
+        .into_iter()
+        .filter(|m| m.working_row == game.floor_row())
+        .min_by_key(|m| game.num_tiles_taken(m).unwrap_or(usize::MAX))
+}
+
+pub struct RandomAgent {
+    rng: Mutex<ChaCha8Rng>,
+}
+
+impl RandomAgent {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(ChaCha8Rng::seed_from_u64(seed)),
+        }
+    }
 }
 
-pub struct RandomAgent {}
 impl Agent for RandomAgent {
     fn choose_action(&self, game: &GameState) -> Move {
         let moves = game.valid_moves();
-        let mut rng = rand::rng();
-        if let Some(m) = moves.choose(&mut rng) {
+        let mut rng = self.rng.lock().unwrap();
+        if let Some(m) = moves.choose(&mut *rng) {
             *m
         } else {
             panic!("No moves to choose from! GameState: {:?}", game);
@@ -28,6 +86,36 @@ impl Agent for RandomAgent {
     }
 }
 
+/// Plays a random move some of the time, and otherwise defers to another
+/// agent.
+pub struct BlunderingAgent {
+    agent: Box<dyn Agent + Send>,
+    blunder_percent: u32,
+    rng: Mutex<ChaCha8Rng>,
+}
+
+impl Agent for BlunderingAgent {
+    fn choose_action(&self, game: &GameState) -> Move {
+        let mut rng = self.rng.lock().unwrap();
+        if rng.random_range(0..100) >= self.blunder_percent {
+            return self.agent.choose_action(game);
+        }
+        match game.valid_moves().choose(&mut *rng) {
+            Some(m) => *m,
+            None => panic!("No moves to choose from! GameState: {:?}", game),
+        }
+    }
+
+    fn choose_placement(
+        &self,
+        game: &GameState,
+        player_idx: usize,
+        row: usize,
+    ) -> Option<usize> {
+        self.agent.choose_placement(game, player_idx, row)
+    }
+}
+
 #[derive(Debug)]
 pub struct GreedyAgent {
     // Value to place on taking the "1st" token, aside from the trash penalty.
@@ -42,6 +130,8 @@ pub struct GreedyAgent {
     num_tiles_bias: i32,
     // Value to place on playing colors in the middle of the board.
     middle_bias: i32,
+    // Value to place on each tile sent to the floor that a partner needs.
+    partner_bias: i32,
 }
 
 impl Agent for GreedyAgent {
@@ -65,6 +155,7 @@ impl GreedyAgent {
             center_bias: 10,
             num_tiles_bias: 10,
             middle_bias: 0,
+            partner_bias: -10,
         }
     }
 
@@ -85,12 +176,27 @@ impl GreedyAgent {
         }
         let num_tiles = game.num_tiles_taken(m).expect("Cannot score move");
         bias += self.num_tiles_bias * num_tiles as i32;
-        let column = played_column(m.working_row, m.color);
-        bias += self.middle_bias * (2 - column as i32);
+        let partner_needs = game
+            .partner_of(game.curr_player_idx)
+            .is_some_and(|idx| game.players[idx].is_collecting(m.color));
+        if partner_needs && m.working_row == game.floor_row() {
+            bias += self.partner_bias * num_tiles as i32;
+        }
+        // Free-placement walls don't fix the column until the round ends.
+        if !game.rules().free_placement {
+            let column =
+                played_column(m.working_row, m.color, game.floor_row());
+            bias += self.middle_bias * (2 - column as i32);
+        }
         player
-            .add_tiles(m.working_row, m.color, num_tiles)
+            .add_tiles_with_wilds(
+                m.working_row,
+                m.color,
+                num_tiles - m.num_wilds,
+                m.num_wilds,
+            )
             .expect("Cannot add tiles");
-        let score = player_score(&mut player);
+        let score = player_score(&mut player, game.rules());
         (score, bias)
     }
 }
@@ -109,39 +215,51 @@ pub struct RoundPlanningAgent {
 
 impl Agent for RoundPlanningAgent {
     fn choose_action(&self, game: &GameState) -> Move {
-        let mut moves = game.valid_moves();
+        let moves = self.candidate_moves(game);
         if moves.is_empty() {
             panic!("No moves to choose from! GameState: {:?}", game);
         }
-        moves.sort_by_cached_key(|m| self.greedy.score_move(game, m));
-        let start = if moves.len() >= self.num_branches {
-            moves.len() - self.num_branches
-        } else {
-            0
-        };
-        let my_idx = game.curr_player_idx;
-        *moves[start..]
+        *moves
             .iter()
-            .max_by_key(|m| {
-                let mut ng = game.clone();
-                if ng.take_turn(m).unwrap() {
-                    player_score(&mut ng.players[my_idx])
-                } else {
-                    self.rollout(my_idx, &mut ng)
-                }
-            })
+            .max_by_key(|m| self.evaluate_move(game, m))
             .unwrap()
     }
 }
 
 impl RoundPlanningAgent {
     pub fn new(recurse: bool) -> Self {
+        Self::with_budget(recurse, 5)
+    }
+
+    /// Creates an agent that only looks ahead from its `num_branches` most
+    /// promising moves.
+    pub fn with_budget(recurse: bool, num_branches: usize) -> Self {
         Self {
-            num_branches: 5,
+            num_branches: num_branches.max(1),
             recurse,
             greedy: GreedyAgent::new(),
         }
     }
+    /// Estimates the current player's score at the end of the round if they
+    /// make move `m` now. In team play, the partner's score counts too.
+    pub fn evaluate_move(&self, game: &GameState, m: &Move) -> i32 {
+        let my_idx = game.curr_player_idx;
+        let mut ng = game.clone();
+        if ng.take_turn(m).unwrap() {
+            team_score(&mut ng, my_idx)
+        } else {
+            self.rollout(my_idx, &mut ng)
+        }
+    }
+
+    /// Returns up to `num_branches` moves that look best to the greedy agent.
+    pub fn candidate_moves(&self, game: &GameState) -> Vec<Move> {
+        let mut moves = game.valid_moves();
+        moves.sort_by_cached_key(|m| self.greedy.score_move(game, m));
+        let start = moves.len().saturating_sub(self.num_branches);
+        moves.split_off(start)
+    }
+
     fn rollout(&self, idx: usize, game: &mut GameState) -> i32 {
         loop {
             let m = if self.recurse && game.curr_player_idx == idx {
@@ -150,14 +268,24 @@ impl RoundPlanningAgent {
                 self.greedy.choose_action(game)
             };
             if game.take_turn(&m).unwrap() {
-                return player_score(&mut game.players[idx]);
+                return team_score(game, idx);
             }
         }
     }
 }
 
-fn player_score(p: &mut PlayerState) -> i32 {
-    p.score_round().expect("Cannot score round");
-    p.score_bonuses();
+fn player_score(p: &mut PlayerState, rules: &RuleSet) -> i32 {
+    p.score_round(rules).expect("Cannot score round");
+    p.score_bonuses(rules);
     p.score()
 }
+
+// Scores the round for a player, plus their partner in team play.
+fn team_score(game: &mut GameState, idx: usize) -> i32 {
+    let rules = game.shared_rules();
+    let partner = game.partner_of(idx);
+    std::iter::once(idx)
+        .chain(partner)
+        .map(|i| player_score(&mut game.players[i], &rules))
+        .sum()
+}
@@ -0,0 +1,203 @@
+use crate::agent::RoundPlanningAgent;
+use crate::game_state::GameState;
+use crate::player_move::Move;
+use serde::{Deserialize, Serialize};
+
+/// Moves losing at least this many points are inaccuracies.
+pub const INACCURACY_LOSS: i32 = 2;
+/// Moves losing at least this many points are blunders.
+pub const BLUNDER_LOSS: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MoveGrade {
+    Best,
+    Good,
+    Inaccuracy,
+    Blunder,
+}
+
+impl MoveGrade {
+    pub fn from_point_loss(loss: i32) -> MoveGrade {
+        if loss <= 0 {
+            MoveGrade::Best
+        } else if loss < INACCURACY_LOSS {
+            MoveGrade::Good
+        } else if loss < BLUNDER_LOSS {
+            MoveGrade::Inaccuracy
+        } else {
+            MoveGrade::Blunder
+        }
+    }
+}
+
+#[test]
+fn grading_point_loss() {
+    assert_eq!(MoveGrade::from_point_loss(0), MoveGrade::Best);
+    assert_eq!(MoveGrade::from_point_loss(1), MoveGrade::Good);
+    assert_eq!(MoveGrade::from_point_loss(2), MoveGrade::Inaccuracy);
+    assert_eq!(MoveGrade::from_point_loss(4), MoveGrade::Inaccuracy);
+    assert_eq!(MoveGrade::from_point_loss(5), MoveGrade::Blunder);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MoveReview {
+    pub turn: usize,
+    pub round: usize,
+    pub player_idx: usize,
+    pub played: Move,
+    pub best: Move,
+    pub played_value: i32,
+    pub best_value: i32,
+    pub point_loss: i32,
+    pub grade: MoveGrade,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerSummary {
+    pub display_name: String,
+    pub num_moves: usize,
+    pub num_best: usize,
+    pub num_inaccuracies: usize,
+    pub num_blunders: usize,
+    pub total_point_loss: i32,
+    // Percentage of moves that were neither inaccuracies nor blunders.
+    pub accuracy: f64,
+}
+
+impl PlayerSummary {
+    fn new(display_name: &str) -> Self {
+        Self {
+            display_name: display_name.to_string(),
+            num_moves: 0,
+            num_best: 0,
+            num_inaccuracies: 0,
+            num_blunders: 0,
+            total_point_loss: 0,
+            accuracy: 100.0,
+        }
+    }
+
+    fn add(&mut self, review: &MoveReview) {
+        self.num_moves += 1;
+        self.total_point_loss += review.point_loss;
+        match review.grade {
+            MoveGrade::Best => self.num_best += 1,
+            MoveGrade::Good => {}
+            MoveGrade::Inaccuracy => self.num_inaccuracies += 1,
+            MoveGrade::Blunder => self.num_blunders += 1,
+        }
+        let num_bad = self.num_inaccuracies + self.num_blunders;
+        self.accuracy =
+            100.0 * (self.num_moves - num_bad) as f64 / self.num_moves as f64;
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameReview {
+    pub moves: Vec<MoveReview>,
+    pub players: Vec<PlayerSummary>,
+}
+
+/// Replays a finished game from its seed and grades every move by how many
+/// end-of-round points it gave up, compared to the best move found by the
+/// strongest agent.
+pub fn review_game(
+    final_state: &GameState,
+    moves: &[Move],
+) -> Result<GameReview, String> {
+    let agent = RoundPlanningAgent::new(true);
+    let mut game = final_state.replay_start()?;
+    let mut players: Vec<PlayerSummary> = game
+        .players
+        .iter()
+        .map(|p| PlayerSummary::new(&p.display_name))
+        .collect();
+    let mut reviews = Vec::with_capacity(moves.len());
+    let mut game_over = false;
+    game.start_round();
+    for (turn, played) in moves.iter().enumerate() {
+        if game_over {
+            return Err(format!(
+                "Move #{} was made after the game ended.",
+                turn
+            ));
+        }
+        // Apply the move first, so that illegal moves are reported as errors.
+        let mut next = game.clone();
+        let round_over = next.take_turn(played)?;
+        let played_value = agent.evaluate_move(&game, played);
+        let mut best = *played;
+        let mut best_value = played_value;
+        for m in agent.candidate_moves(&game) {
+            let value = agent.evaluate_move(&game, &m);
+            if value > best_value {
+                best = m;
+                best_value = value;
+            }
+        }
+        let point_loss = best_value - played_value;
+        let review = MoveReview {
+            turn,
+            round: game.round_number(),
+            player_idx: game.curr_player_idx,
+            played: *played,
+            best,
+            played_value,
+            best_value,
+            point_loss,
+            grade: MoveGrade::from_point_loss(point_loss),
+        };
+        players[review.player_idx].add(&review);
+        reviews.push(review);
+        game = next;
+        if round_over {
+            game_over = game.finish_round()?;
+            if !game_over {
+                game.start_round();
+            }
+        }
+    }
+    Ok(GameReview {
+        moves: reviews,
+        players,
+    })
+}
+
+#[test]
+fn reviewing_a_game() {
+    use crate::agent::{Agent, GreedyAgent};
+    use rand::SeedableRng;
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(42);
+    let mut game = GameState::new(&["ann", "bob"], &mut rng).unwrap();
+    let agent = GreedyAgent::new();
+    let mut moves = vec![];
+    game.start_round();
+    // Play a single round so the test stays fast.
+    loop {
+        let m = agent.choose_action(&game);
+        moves.push(m);
+        if game.take_turn(&m).unwrap() {
+            break;
+        }
+    }
+    let review = review_game(&game, &moves).unwrap();
+    assert_eq!(review.moves.len(), moves.len());
+    assert_eq!(review.players.len(), 2);
+    assert_eq!(review.players[0].display_name, "ann");
+    let num_reviewed: usize = review.players.iter().map(|p| p.num_moves).sum();
+    assert_eq!(num_reviewed, moves.len());
+    for r in &review.moves {
+        assert!(r.point_loss >= 0);
+        assert!(r.best_value >= r.played_value);
+    }
+
+    // Moves that don't match the replayed game are rejected.
+    let bogus = Move {
+        factory_idx: 0,
+        color: crate::colors::Color::Blue,
+        working_row: 0,
+        num_wilds: 0,
+    };
+    assert!(review_game(&game, &[bogus]).is_err());
+}
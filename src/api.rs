@@ -1,16 +1,114 @@
 use blau_api::{DynSafeGameAPI, GameAPI, PlayerInfo, Result};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use crate::{
-    agent::{Agent, create_agent},
-    game_state::GameState,
+    agent::{
+        Agent, create_agent, create_handicapped_agent, smallest_floor_move,
+    },
+    colors::Color,
+    game_state::{GameState, RoundResult, Standing, TurnDelta},
     player_move,
+    rules::{Handicap, RuleSet},
+    scoring::{Bonus, RoundScore},
 };
 
 /// Parameters for game initialization.
 #[derive(Deserialize)]
 struct GameParams {
     tutor_mode: bool,
+    // Difficulty of the tutor agent.
+    #[serde(default = "default_tutor_level")]
+    tutor_level: usize,
+    // IDs of the human players who get hints. None means all humans.
+    #[serde(default)]
+    tutored_players: Option<Vec<String>>,
+    // If true, hints are only sent in response to a "hint" action.
+    #[serde(default)]
+    hints_on_demand: bool,
+    // If true, moves that don't end a round are sent as deltas.
+    #[serde(default)]
+    delta_updates: bool,
+    // IDs of observers who receive notices but don't play.
+    #[serde(default)]
+    spectators: Vec<String>,
+    // Number of moves that spectator notices lag behind the game.
+    #[serde(default)]
+    spectator_delay: usize,
+    // Difficulty of the agent that takes over for players who abandon the
+    // game. If None, abandoning counts as resigning.
+    #[serde(default)]
+    takeover_level: Option<usize>,
+    // Per-player time controls. None means untimed.
+    #[serde(default)]
+    clock: Option<ClockParams>,
+    // House rules, either as a preset name or a full rule set.
+    #[serde(default)]
+    rules: RulesParam,
+    // In team play, also show each tutor hint to the player's partner.
+    #[serde(default)]
+    partner_hints: bool,
+    // Handicaps for individual players, by ID.
+    #[serde(default)]
+    handicaps: HashMap<String, Handicap>,
+    // Fixes the tile draws, so that games can be dealt identically.
+    #[serde(default)]
+    seed: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RulesParam {
+    Preset(String),
+    Custom(RuleSet),
+}
+
+impl Default for RulesParam {
+    fn default() -> Self {
+        RulesParam::Custom(RuleSet::default())
+    }
+}
+
+/// Time controls for timed games.
+#[derive(Deserialize, Clone, Copy)]
+struct ClockParams {
+    // Starting time for each player.
+    total_ms: u64,
+    // Time added to a player's clock after each of their moves.
+    #[serde(default)]
+    increment_ms: u64,
+    // Difficulty of the agent that moves for players who run out of time.
+    // If None, they move all tiles of the smallest pile to the floor.
+    #[serde(default)]
+    timeout_level: Option<usize>,
+}
+
+fn default_tutor_level() -> usize {
+    2
+}
+
+impl Default for GameParams {
+    fn default() -> Self {
+        Self {
+            tutor_mode: false,
+            tutor_level: default_tutor_level(),
+            tutored_players: None,
+            hints_on_demand: false,
+            delta_updates: false,
+            spectators: vec![],
+            spectator_delay: 0,
+            takeover_level: None,
+            clock: None,
+            rules: RulesParam::default(),
+            partner_hints: false,
+            handicaps: HashMap::new(),
+            seed: None,
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -18,6 +116,13 @@ pub struct MoveMessage {
     color: usize,
     factory: usize,
     working: usize,
+    // Wild tiles taken to stand in for the color.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    wilds: usize,
+}
+
+fn is_zero(n: &usize) -> bool {
+    *n == 0
 }
 
 impl MoveMessage {
@@ -27,6 +132,7 @@ impl MoveMessage {
             color,
             factory_idx: self.factory,
             working_row: self.working,
+            num_wilds: self.wilds,
         })
     }
     pub fn from_move(mv: &player_move::Move) -> Self {
@@ -34,28 +140,179 @@ impl MoveMessage {
             color: mv.color as usize,
             factory: mv.factory_idx,
             working: mv.working_row,
+            wilds: mv.num_wilds,
         }
     }
 }
 
+/// Non-move actions a player can send. `process_action` doesn't know who
+/// sent an action, so it acts for whichever player or spectator the action
+/// names. Hosts must check that `player_id` or `spectator_id` is the
+/// sender's own, or any client could resign for an opponent.
+#[derive(Deserialize)]
+#[serde(tag = "action")]
+#[serde(rename_all = "lowercase")]
+enum CommandMessage {
+    Hint,
+    // Asks for the full game state to be sent to a player.
+    Resync {
+        player_id: String,
+    },
+    Spectate {
+        spectator_id: String,
+    },
+    Unspectate {
+        spectator_id: String,
+    },
+    Resign {
+        player_id: String,
+    },
+    Abandon {
+        player_id: String,
+    },
+    // Chooses the wall column for a completed row, in free-placement games.
+    Place {
+        player_id: String,
+        row: usize,
+        column: usize,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ActionMessage {
+    Move(MoveMessage),
+    Command(CommandMessage),
+}
+
+/// A hint that the tutor showed to a player.
+#[derive(Deserialize, Serialize, Clone)]
+struct HintRecord {
+    // Number of moves made before the hint was shown.
+    turn: usize,
+    player_idx: usize,
+    #[serde(flatten)]
+    hint: MoveMessage,
+}
+
+/// Everything that is stored for a finished game.
+#[derive(Deserialize, Serialize)]
+struct GameRecord {
+    state: GameState,
+    #[serde(default)]
+    moves: Vec<MoveMessage>,
+    #[serde(default)]
+    hints: Vec<HintRecord>,
+}
+
 #[derive(Serialize)]
 struct TakeTurnMessage {
     #[serde(flatten)]
     move_: MoveMessage,
     player_idx: usize,
     round_over: bool,
+    // Number of moves made so far, so clients can detect missed updates.
+    seq: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_data: Option<GameState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    delta: Option<TurnDelta>,
+    // Time left for each player, in timed games.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    clocks_ms: Option<Vec<u64>>,
+}
+
+#[derive(Serialize)]
+struct EndMessage {
+    // The final move, unless the game ended by resignation or after wall
+    // placements were chosen.
+    #[serde(flatten)]
+    turn: Option<TakeTurnMessage>,
+    // The sole winner, or None if first place is shared. Kept for clients
+    // that predate shared wins.
     winner: Option<String>,
-    game_data: GameState,
+    // All players who share first place.
+    winners: Vec<String>,
+    ranking: Vec<Standing>,
+}
+
+#[derive(Serialize)]
+struct PlayerRoundEnd {
+    player_idx: usize,
+    // Wall placements, floor penalties and points earned this round.
+    #[serde(flatten)]
+    score: RoundScore,
+    // Tiles that went to the box lid.
+    discarded: Vec<Color>,
+    total_score: i32,
+}
+
+#[derive(Serialize)]
+struct RoundEndMessage {
+    round: usize,
+    players: Vec<PlayerRoundEnd>,
+    // Factory tiles for the next round. Empty if the game is over.
+    factories: Vec<Vec<Color>>,
+}
+
+#[derive(Serialize)]
+struct PlayerGameEnd {
+    player_idx: usize,
+    bonuses: Vec<Bonus>,
+    total_score: i32,
+}
+
+#[derive(Serialize)]
+struct GameEndMessage {
+    players: Vec<PlayerGameEnd>,
 }
 
 #[derive(Serialize)]
 #[serde(tag = "action")]
 #[serde(rename_all = "lowercase")]
 enum GameMessage {
-    Start { game_id: i64 },
+    Start {
+        game_id: i64,
+    },
+    Sync {
+        seq: usize,
+        game_data: GameState,
+    },
     Tutor(MoveMessage),
+    // A hint shown to the player's partner, in team play.
+    #[serde(rename = "partner_hint")]
+    PartnerHint {
+        player_idx: usize,
+        #[serde(flatten)]
+        hint: MoveMessage,
+    },
     Play(TakeTurnMessage),
-    End(TakeTurnMessage),
+    End(EndMessage),
+    #[serde(rename = "round_end")]
+    RoundEnd(RoundEndMessage),
+    #[serde(rename = "game_end")]
+    GameEnd(GameEndMessage),
+    Resign {
+        player_idx: usize,
+    },
+    Takeover {
+        player_idx: usize,
+        level: usize,
+    },
+    Timeout {
+        player_idx: usize,
+    },
+    // Asks a player to choose a wall column for a completed row.
+    Place {
+        player_idx: usize,
+        row: usize,
+        columns: Vec<usize>,
+    },
+    Placed {
+        player_idx: usize,
+        row: usize,
+        column: usize,
+    },
 }
 
 pub struct BlauAPI {
@@ -69,51 +326,392 @@ pub struct BlauAPI {
     game_over: bool,
     // If Some(i), agents[i] is the tutor.
     tutor_idx: Option<usize>,
+    // Indicates which players receive hints from the tutor.
+    tutored: Vec<bool>,
+    // If true, hints are only sent when requested.
+    hints_on_demand: bool,
+    // If true, partners see each other's hints.
+    partner_hints: bool,
+    // If true, send deltas instead of the full state when possible.
+    delta_updates: bool,
+    // IDs of observers who receive redacted notices
+    spectators: Vec<String>,
+    // Number of moves that spectator notices lag behind the game.
+    spectator_delay: usize,
+    // Redacted notices waiting for the delay, with the move count when sent.
+    spectator_queue: VecDeque<(usize, String)>,
+    // Difficulty of the agent that replaces players who abandon the game.
+    takeover_level: Option<usize>,
+    // Time controls, if the game is timed.
+    clock: Option<ClockParams>,
+    // Time left for each player, as of the start of the current turn.
+    clocks: Vec<Duration>,
+    // When the current player's turn started.
+    turn_started: Instant,
+    // All moves made so far, in order.
+    moves: Vec<MoveMessage>,
+    // All hints shown so far, in order.
+    hints: Vec<HintRecord>,
 }
 
 impl BlauAPI {
     fn do_action<F: FnMut(&str, &str)>(
         &mut self,
         action: &MoveMessage,
+        now: Instant,
         mut notice_cb: F,
     ) -> Result<()> {
         let player_idx = self.state.curr_player_idx;
-        let round_over = self.state.take_turn(&action.to_move()?)?;
-        let mut winner = None;
-        if round_over {
-            self.game_over = self.state.finish_round()?;
-            if self.game_over {
-                let max_idx = self
-                    .player_scores()
-                    .iter()
-                    .enumerate()
-                    .max_by_key(|&(_idx, score)| score)
-                    .map(|(idx, _score)| idx)
-                    .ok_or("No players to determine winner")?;
-                winner = Some(self.player_ids[max_idx].clone());
-            } else {
-                self.state.start_round();
-            }
+        let (round_over, delta) =
+            self.state.take_turn_with_delta(&action.to_move()?)?;
+        self.moves.push(action.clone());
+        self.charge_clock(player_idx, now);
+        // Free-placement games score the round once walls are placed.
+        let placing = round_over && self.state.pending_placement().is_some();
+        let mut round_end = None;
+        if round_over && !placing {
+            round_end = Some(self.end_round()?);
         }
         // Notify all human players of the action.
+        let use_delta = self.delta_updates && !round_over;
         let turn_msg = TakeTurnMessage {
             move_: action.clone(),
             player_idx,
             round_over,
-            winner,
-            game_data: self.state.clone(),
+            seq: self.moves.len(),
+            game_data: (!use_delta).then(|| self.state.clone()),
+            delta: use_delta.then_some(delta),
+            clocks_ms: self.clock.map(|_| {
+                self.clocks.iter().map(|c| c.as_millis() as u64).collect()
+            }),
         };
         let msg = if self.game_over {
-            GameMessage::End(turn_msg)
+            GameMessage::End(self.end_message(Some(turn_msg)))
         } else {
             GameMessage::Play(turn_msg)
         };
-        let msg = serde_json::to_string(&msg)?;
+        self.broadcast(&msg, &mut notice_cb)?;
+        if let Some(round_end) = round_end {
+            let msg = GameMessage::RoundEnd(round_end);
+            self.broadcast(&msg, &mut notice_cb)?;
+        }
+        if self.game_over {
+            let msg = GameMessage::GameEnd(self.game_end_message());
+            self.broadcast(&msg, &mut notice_cb)?;
+        }
+        if placing {
+            self.resolve_placements(&mut notice_cb)?;
+        }
+        Ok(())
+    }
+    /// Scores the finished round and deals the next one, unless the game is
+    /// over.
+    fn end_round(&mut self) -> Result<RoundEndMessage> {
+        let round = self.state.round_number();
+        let result = self.state.finish_round_detailed()?;
+        self.game_over = result.game_over;
+        if !self.game_over {
+            self.state.start_round();
+        }
+        Ok(self.round_end_message(round, result))
+    }
+    /// Makes wall placements for agents until a human has to choose, then
+    /// asks them. Ends the round once every placement has been made.
+    fn resolve_placements<F: FnMut(&str, &str)>(
+        &mut self,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        while let Some((player_idx, row)) = self.state.pending_placement() {
+            let Some(agent) = &self.agents[player_idx] else {
+                let columns =
+                    self.state.players[player_idx].placement_options(row);
+                let msg = GameMessage::Place {
+                    player_idx,
+                    row,
+                    columns,
+                };
+                return self.broadcast(&msg, &mut notice_cb);
+            };
+            let column = agent
+                .choose_placement(&self.state, player_idx, row)
+                .ok_or("No column to place in")?;
+            self.state.place_tile(player_idx, row, column)?;
+            let msg = GameMessage::Placed {
+                player_idx,
+                row,
+                column,
+            };
+            self.broadcast(&msg, &mut notice_cb)?;
+        }
+        let round_end = self.end_round()?;
+        if self.game_over {
+            let msg = GameMessage::End(self.end_message(None));
+            self.broadcast(&msg, &mut notice_cb)?;
+        }
+        let msg = GameMessage::RoundEnd(round_end);
+        self.broadcast(&msg, &mut notice_cb)?;
+        if self.game_over {
+            let msg = GameMessage::GameEnd(self.game_end_message());
+            self.broadcast(&msg, &mut notice_cb)?;
+        }
+        // Placements don't count against the next player's clock.
+        self.turn_started = Instant::now();
+        Ok(())
+    }
+    /// Handles a human player's choice of wall column.
+    fn place_tile<F: FnMut(&str, &str)>(
+        &mut self,
+        player_id: &str,
+        row: usize,
+        column: usize,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let player_idx = self.human_player_idx(player_id)?;
+        if self.state.pending_placement() != Some((player_idx, row)) {
+            return Err(format!("{player_id} can't place row {row} now").into());
+        }
+        self.state.place_tile(player_idx, row, column)?;
+        let msg = GameMessage::Placed {
+            player_idx,
+            row,
+            column,
+        };
+        self.broadcast(&msg, &mut notice_cb)?;
+        self.resolve_placements(&mut notice_cb)?;
+        self.process_agents(notice_cb)
+    }
+    /// Deducts the time a player spent on their move, then starts the clock
+    /// for the next player.
+    fn charge_clock(&mut self, player_idx: usize, now: Instant) {
+        if let Some(clock) = self.clock {
+            let used = now.saturating_duration_since(self.turn_started);
+            let left = &mut self.clocks[player_idx];
+            *left = left.saturating_sub(used)
+                + Duration::from_millis(clock.increment_ms);
+        }
+        self.turn_started = now;
+    }
+    /// Moves, or chooses wall columns, for any human player who has run out
+    /// of time as of `now`.
+    /// Returns true if any player timed out.
+    pub fn check_timeouts<F: FnMut(&str, &str)>(
+        &mut self,
+        now: Instant,
+        mut notice_cb: F,
+    ) -> Result<bool> {
+        let Some(clock) = self.clock else {
+            return Ok(false);
+        };
+        let mut timed_out = false;
+        while !self.game_over {
+            // Humans choosing a wall column are on the clock too.
+            let placement = self.state.pending_placement();
+            if placement.is_none() && self.state.is_round_over() {
+                break;
+            }
+            let player_idx =
+                placement.map_or(self.state.curr_player_idx, |(idx, _)| idx);
+            let used = now.saturating_duration_since(self.turn_started);
+            if self.agents[player_idx].is_some()
+                || self.clocks[player_idx] > used
+            {
+                break;
+            }
+            timed_out = true;
+            let msg = GameMessage::Timeout { player_idx };
+            self.broadcast(&msg, &mut notice_cb)?;
+            if let Some((player_idx, row)) = placement {
+                let column = self.state.players[player_idx]
+                    .best_placement(row)
+                    .ok_or("No column to place in")?;
+                self.state.place_tile(player_idx, row, column)?;
+                let msg = GameMessage::Placed {
+                    player_idx,
+                    row,
+                    column,
+                };
+                self.broadcast(&msg, &mut notice_cb)?;
+                self.resolve_placements(&mut notice_cb)?;
+                // Further rows of a player out of time are placed too.
+                if self.state.pending_placement().is_none() {
+                    self.process_agents(&mut notice_cb)?;
+                    self.turn_started = self.turn_started.max(now);
+                }
+                continue;
+            }
+            let mv = match clock.timeout_level {
+                Some(level) => create_agent(level).choose_action(&self.state),
+                None => smallest_floor_move(&self.state)
+                    .ok_or("No moves to choose from")?,
+            };
+            let mv = MoveMessage::from_move(&mv);
+            self.do_action(&mv, now, &mut notice_cb)?;
+            self.process_agents(&mut notice_cb)?;
+            // The next turn starts after the timeout and any agent moves,
+            // whichever is later.
+            self.turn_started = self.turn_started.max(now);
+        }
+        Ok(timed_out)
+    }
+    /// Handles a human player resigning or abandoning the game.
+    fn leave_game<F: FnMut(&str, &str)>(
+        &mut self,
+        player_id: &str,
+        resign: bool,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let player_idx = self.human_player_idx(player_id)?;
+        // Other players' turns go on as they were, hints and all.
+        let was_current = self.state.curr_player_idx == player_idx;
+        if let (false, Some(level)) = (resign, self.takeover_level) {
+            self.agents[player_idx] = Some(create_agent(level));
+            self.tutored[player_idx] = false;
+            let msg = GameMessage::Takeover { player_idx, level };
+            self.broadcast(&msg, &mut notice_cb)?;
+            return self.resume_after_leaving(was_current, notice_cb);
+        }
+        self.game_over = self.state.resign(player_idx)?;
+        let msg = GameMessage::Resign { player_idx };
+        self.broadcast(&msg, &mut notice_cb)?;
+        if self.game_over {
+            let msg = GameMessage::End(self.end_message(None));
+            self.broadcast(&msg, &mut notice_cb)?;
+            let msg = GameMessage::GameEnd(self.game_end_message());
+            self.broadcast(&msg, &mut notice_cb)?;
+            return Ok(());
+        }
+        self.resume_after_leaving(was_current, notice_cb)
+    }
+    // Moves the game on after a player leaves, if they were holding it up.
+    fn resume_after_leaving<F: FnMut(&str, &str)>(
+        &mut self,
+        was_current: bool,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let round_over = self.state.is_round_over();
+        if round_over {
+            self.resolve_placements(&mut notice_cb)?;
+        }
+        if !was_current && !round_over {
+            return Ok(());
+        }
+        self.process_agents(notice_cb)
+    }
+    fn end_message(&self, turn: Option<TakeTurnMessage>) -> EndMessage {
+        let winners = self.winner_ids();
+        EndMessage {
+            turn,
+            winner: match winners.as_slice() {
+                [winner] => Some(winner.clone()),
+                _ => None,
+            },
+            winners,
+            ranking: self.state.ranking(),
+        }
+    }
+    fn round_end_message(
+        &self,
+        round: usize,
+        result: RoundResult,
+    ) -> RoundEndMessage {
+        let players = self
+            .state
+            .players
+            .iter()
+            .zip(result.discarded)
+            .enumerate()
+            .map(|(player_idx, (p, discarded))| PlayerRoundEnd {
+                player_idx,
+                score: p.ledger().rounds.last().cloned().unwrap_or_default(),
+                discarded,
+                total_score: p.score(),
+            })
+            .collect();
+        let factories = if result.game_over {
+            vec![]
+        } else {
+            self.state.factories().to_vec()
+        };
+        RoundEndMessage {
+            round,
+            players,
+            factories,
+        }
+    }
+    fn game_end_message(&self) -> GameEndMessage {
+        let players = self
+            .state
+            .players
+            .iter()
+            .enumerate()
+            .map(|(player_idx, p)| PlayerGameEnd {
+                player_idx,
+                bonuses: p.ledger().bonuses.clone(),
+                total_score: p.score(),
+            })
+            .collect();
+        GameEndMessage { players }
+    }
+    pub fn add_spectator(&mut self, spectator_id: &str) -> Result<()> {
+        if self.player_ids.iter().any(|id| id == spectator_id) {
+            return Err(format!("{spectator_id} is a player").into());
+        }
+        if !self.spectators.iter().any(|id| id == spectator_id) {
+            self.spectators.push(spectator_id.to_string());
+        }
+        Ok(())
+    }
+    pub fn remove_spectator(&mut self, spectator_id: &str) {
+        self.spectators.retain(|id| id != spectator_id);
+    }
+    /// Sends a notice to all human players, and a redacted copy to spectators.
+    fn broadcast<F: FnMut(&str, &str)>(
+        &mut self,
+        msg: &GameMessage,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let msg = serde_json::to_string(msg)?;
         for idx in self.human_player_idxs() {
             notice_cb(self.player_ids[idx].as_str(), &msg);
         }
+        if !self.spectators.is_empty() {
+            let redacted = redact_for_spectators(&msg)?;
+            self.spectator_queue.push_back((self.moves.len(), redacted));
+        }
+        self.flush_spectators(notice_cb);
         Ok(())
     }
+    /// Sends spectators every queued notice that is old enough.
+    fn flush_spectators<F: FnMut(&str, &str)>(&mut self, mut notice_cb: F) {
+        while let Some((sent_at, _)) = self.spectator_queue.front() {
+            if !self.game_over
+                && sent_at + self.spectator_delay > self.moves.len()
+            {
+                break;
+            }
+            let (_, msg) = self.spectator_queue.pop_front().unwrap();
+            for id in &self.spectators {
+                notice_cb(id, &msg);
+            }
+        }
+    }
+    /// Returns the IDs of all players who share first place.
+    pub fn winner_ids(&self) -> Vec<String> {
+        self.state
+            .winners()
+            .into_iter()
+            .map(|idx| self.player_ids[idx].clone())
+            .collect()
+    }
+    fn human_player_idx(&self, player_id: &str) -> Result<usize> {
+        let idx = self
+            .player_ids
+            .iter()
+            .position(|id| id == player_id)
+            .filter(|&idx| self.agents[idx].is_none())
+            .ok_or_else(|| format!("{player_id} is not a human player"))?;
+        Ok(idx)
+    }
     fn human_player_idxs(&self) -> impl Iterator<Item = usize> + '_ {
         self.agents.iter().enumerate().filter_map(|(idx, agent)| {
             if agent.is_none() { Some(idx) } else { None }
@@ -123,66 +721,203 @@ impl BlauAPI {
         &mut self,
         mut notice_cb: F,
     ) -> Result<()> {
-        while !self.game_over {
+        // Agents wait while humans choose wall placements.
+        while !self.game_over && !self.state.is_round_over() {
             if let Some(ai) = &self.agents[self.state.curr_player_idx] {
                 let mv = ai.choose_action(&self.state);
-                self.do_action(&MoveMessage::from_move(&mv), &mut notice_cb)?;
-            } else if let Some(tutor_idx) = self.tutor_idx {
-                let tutor = self.agents[tutor_idx]
-                    .as_ref()
-                    .expect("Tutor agent missing");
-                let tutor_mv = tutor.choose_action(&self.state);
-                // Send tutor move to human player.
-                let msg = GameMessage::Tutor(MoveMessage::from_move(&tutor_mv));
-                let msg = serde_json::to_string(&msg)?;
-                notice_cb(self.current_player_id(), &msg);
-                break;
+                let mv = MoveMessage::from_move(&mv);
+                // Agents are charged for their own thinking time.
+                self.do_action(&mv, Instant::now(), &mut notice_cb)?;
             } else {
                 // Next player is human.
+                if !self.hints_on_demand {
+                    self.send_hint(&mut notice_cb)?;
+                }
+                // Their clock starts once the agents are done.
+                self.turn_started = Instant::now();
                 break;
             }
         }
         Ok(())
     }
+    fn send_hint<F: FnMut(&str, &str)>(
+        &mut self,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let Some(tutor_idx) = self.tutor_idx else {
+            return Ok(());
+        };
+        if self.state.is_round_over() {
+            return Ok(());
+        }
+        if !self.tutored[self.state.curr_player_idx] {
+            return Ok(());
+        }
+        let tutor = self.agents[tutor_idx]
+            .as_ref()
+            .expect("Tutor agent missing");
+        let hint = MoveMessage::from_move(&tutor.choose_action(&self.state));
+        let player_idx = self.state.curr_player_idx;
+        self.hints.push(HintRecord {
+            turn: self.moves.len(),
+            player_idx,
+            hint: hint.clone(),
+        });
+        // Send tutor move to human player.
+        let msg = GameMessage::Tutor(hint.clone());
+        let msg = serde_json::to_string(&msg)?;
+        notice_cb(self.current_player_id(), &msg);
+        let partner = self
+            .state
+            .partner_of(player_idx)
+            .filter(|&idx| self.partner_hints && self.agents[idx].is_none());
+        if let Some(partner) = partner {
+            let msg = GameMessage::PartnerHint { player_idx, hint };
+            let msg = serde_json::to_string(&msg)?;
+            notice_cb(&self.player_ids[partner], &msg);
+        }
+        Ok(())
+    }
+}
+/// Strips the RNG state from a notice, since it reveals upcoming tile draws.
+fn redact_for_spectators(msg: &str) -> Result<String> {
+    let mut value: serde_json::Value = serde_json::from_str(msg)?;
+    if let Some(data) =
+        value.get_mut("game_data").and_then(|d| d.as_object_mut())
+    {
+        data.remove("rng");
+    }
+    Ok(value.to_string())
 }
+
 impl GameAPI for BlauAPI {
     fn init(players: &[PlayerInfo], params: Option<&str>) -> Result<Self> {
         let params: GameParams = match params {
             Some(p) => serde_json::from_str(p)?,
-            None => GameParams { tutor_mode: false },
+            None => GameParams::default(),
         };
-        let mut rng = rand::rng();
         let player_names: Vec<&str> =
             players.iter().map(|p| p.id.as_str()).collect();
-        let state = GameState::new(&player_names, &mut rng);
+        let rules = match params.rules {
+            RulesParam::Preset(name) => RuleSet::preset(&name)?,
+            RulesParam::Custom(rules) => rules,
+        };
+        let seed: [u8; 32] = match params.seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed).random(),
+            None => rand::rng().random(),
+        };
+        let mut state =
+            GameState::from_seed(&player_names, Arc::new(rules), seed)?;
+        for (id, handicap) in &params.handicaps {
+            let idx = players
+                .iter()
+                .position(|p| &p.id == id)
+                .ok_or_else(|| format!("Unknown player: {id}"))?;
+            if players[idx].level.is_none() && handicap.limits_bot() {
+                return Err(format!("{id} is not a bot").into());
+            }
+            state.set_handicap(idx, handicap.clone())?;
+        }
         let player_ids = players.iter().map(|p| p.id.clone()).collect();
+        // Bots draw from their own stream of the game seed, so that seeded
+        // games play out the same way every time.
+        let mut agent_rng = ChaCha8Rng::from_seed(seed);
+        agent_rng.set_stream(1);
         let mut agents = players
             .iter()
-            .map(|p| p.level.map(|lvl| create_agent(lvl as usize)))
+            .zip(&state.players)
+            .map(|(p, ps)| {
+                let agent_seed = agent_rng.random();
+                p.level.map(|lvl| {
+                    create_handicapped_agent(
+                        lvl as usize,
+                        ps.handicap(),
+                        agent_seed,
+                    )
+                })
+            })
             .collect::<Vec<_>>();
+        let mut tutored: Vec<bool> = players
+            .iter()
+            .map(|p| params.tutor_mode && p.level.is_none())
+            .collect();
+        if let Some(ids) = &params.tutored_players {
+            for (idx, p) in players.iter().enumerate() {
+                tutored[idx] &= ids.contains(&p.id);
+            }
+            for id in ids {
+                if !players.iter().any(|p| &p.id == id && p.level.is_none()) {
+                    return Err(format!("{id} is not a human player").into());
+                }
+            }
+        }
         let tutor_idx = if params.tutor_mode {
-            agents.push(Some(create_agent(2))); // Tutor agent
+            // Tutor agent
+            agents.push(Some(create_agent(params.tutor_level)));
             Some(agents.len() - 1)
         } else {
             None
         };
-        Ok(Self {
+        let mut api = Self {
             state,
             player_ids,
             agents,
             game_over: false,
             tutor_idx,
-        })
+            tutored,
+            hints_on_demand: params.hints_on_demand,
+            partner_hints: params.partner_hints,
+            delta_updates: params.delta_updates,
+            spectators: vec![],
+            spectator_delay: params.spectator_delay,
+            spectator_queue: VecDeque::new(),
+            takeover_level: params.takeover_level,
+            clock: params.clock,
+            clocks: vec![
+                Duration::from_millis(
+                    params.clock.map_or(0, |c| c.total_ms)
+                );
+                players.len()
+            ],
+            turn_started: Instant::now(),
+            moves: vec![],
+            hints: vec![],
+        };
+        for id in &params.spectators {
+            api.add_spectator(id)?;
+        }
+        Ok(api)
     }
 
     fn restore(player_info: &[PlayerInfo], final_state: &str) -> Result<Self> {
-        let fs: GameState = serde_json::from_str(final_state)?;
+        let record = match serde_json::from_str(final_state) {
+            Ok(record) => record,
+            // Older records contain only the game state.
+            Err(_) => GameRecord {
+                state: serde_json::from_str(final_state)?,
+                moves: vec![],
+                hints: vec![],
+            },
+        };
         Ok(Self {
-            state: fs,
+            state: record.state,
             player_ids: player_info.iter().map(|p| p.id.clone()).collect(),
             agents: vec![], // No agents in restored game.
             game_over: true,
-            tutor_idx: None, // Tutor games are not stored.
+            tutor_idx: None, // No tutor needed after the game.
+            tutored: vec![],
+            hints_on_demand: false,
+            partner_hints: false,
+            delta_updates: false,
+            spectators: vec![],
+            spectator_delay: 0,
+            spectator_queue: VecDeque::new(),
+            takeover_level: None,
+            clock: None,
+            clocks: vec![],
+            turn_started: Instant::now(),
+            moves: record.moves,
+            hints: record.hints,
         })
     }
     fn start<F: FnMut(&str, &str)>(
@@ -192,11 +927,9 @@ impl GameAPI for BlauAPI {
     ) -> Result<()> {
         self.state.start_round();
         let msg = GameMessage::Start { game_id };
-        let msg = serde_json::to_string(&msg)?;
-        for idx in self.human_player_idxs() {
-            notice_cb(self.player_ids[idx].as_str(), &msg);
-        }
+        self.broadcast(&msg, &mut notice_cb)?;
         // Advance to wait for the next player action.
+        self.turn_started = Instant::now();
         self.process_agents(notice_cb)?;
         Ok(())
     }
@@ -209,8 +942,54 @@ impl GameAPI for BlauAPI {
         if self.game_over {
             return Err("Game is over".into());
         }
-        let action: MoveMessage = serde_json::from_str(action)?;
-        self.do_action(&action, &mut notice_cb)?;
+        let action = match serde_json::from_str(action)? {
+            ActionMessage::Move(mv) => mv,
+            ActionMessage::Command(CommandMessage::Hint) => {
+                if !self.tutored[self.state.curr_player_idx] {
+                    return Err("Hints are not enabled for this player".into());
+                }
+                return self.send_hint(notice_cb);
+            }
+            ActionMessage::Command(CommandMessage::Resync { player_id }) => {
+                if !self.player_ids.contains(&player_id) {
+                    return Err(format!("Unknown player: {player_id}").into());
+                }
+                let msg = GameMessage::Sync {
+                    seq: self.moves.len(),
+                    game_data: self.state.clone(),
+                };
+                notice_cb(&player_id, &serde_json::to_string(&msg)?);
+                return Ok(());
+            }
+            ActionMessage::Command(CommandMessage::Spectate {
+                spectator_id,
+            }) => {
+                return self.add_spectator(&spectator_id);
+            }
+            ActionMessage::Command(CommandMessage::Unspectate {
+                spectator_id,
+            }) => {
+                self.remove_spectator(&spectator_id);
+                return Ok(());
+            }
+            ActionMessage::Command(CommandMessage::Resign { player_id }) => {
+                return self.leave_game(&player_id, true, notice_cb);
+            }
+            ActionMessage::Command(CommandMessage::Abandon { player_id }) => {
+                return self.leave_game(&player_id, false, notice_cb);
+            }
+            ActionMessage::Command(CommandMessage::Place {
+                player_id,
+                row,
+                column,
+            }) => {
+                return self.place_tile(&player_id, row, column, notice_cb);
+            }
+        };
+        if self.state.is_round_over() {
+            return Err("Waiting for wall placements".into());
+        }
+        self.do_action(&action, Instant::now(), &mut notice_cb)?;
         // Advance to wait for the next player action.
         self.process_agents(&mut notice_cb)?;
         Ok(())
@@ -226,7 +1005,12 @@ impl DynSafeGameAPI for BlauAPI {
         if !self.game_over {
             return Err("Game is not finished".into());
         }
-        Ok(serde_json::to_string(&self.state)?)
+        let record = GameRecord {
+            state: self.state.clone(),
+            moves: self.moves.clone(),
+            hints: self.hints.clone(),
+        };
+        Ok(serde_json::to_string(&record)?)
     }
 
     fn player_view(&self, _player_id: &str) -> Result<String> {
@@ -237,12 +1021,15 @@ impl DynSafeGameAPI for BlauAPI {
         self.player_ids[self.state.curr_player_idx].as_str()
     }
 
+    // In team play, partners both get their team's combined score.
     fn player_scores(&self) -> Vec<i32> {
-        self.state.players.iter().map(|p| p.score()).collect()
+        (0..self.state.players.len())
+            .map(|idx| self.state.team_score(idx))
+            .collect()
     }
 
     fn should_persist(&self) -> bool {
-        self.tutor_idx.is_none()
+        true
     }
 }
 
@@ -254,7 +1041,7 @@ fn exercise_api() {
     ];
     let mut game: BlauAPI =
         GameAPI::init(&players, Some(r#"{"tutor_mode": true}"#)).unwrap();
-    assert!(!game.should_persist());
+    assert!(game.should_persist());
 
     let mut num_notices = 0;
     game.start(1234, |id, msg| {
@@ -294,3 +1081,596 @@ fn exercise_api() {
     // One for us, one for the bot move, one for the tutor.
     assert_eq!(num_notices, 3);
 }
+
+#[test]
+fn tutor_options() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+        PlayerInfo::ai("bot".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": true, "tutor_level": 0,
+                     "tutored_players": ["bar"], "hints_on_demand": true}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    assert_eq!(game.tutored, vec![false, true, false]);
+
+    // No hints are sent unprompted.
+    let mut notices = vec![];
+    game.start(1, |id, msg| notices.push((id.to_string(), msg.to_string())))
+        .unwrap();
+    assert!(notices.iter().all(|(_, msg)| !msg.contains("tutor")));
+
+    // Play until "bar" is up, then ask for a hint.
+    while game.current_player_id() != "bar" {
+        let mv = game.state.valid_moves()[0];
+        let action = serde_json::to_string(&MoveMessage::from_move(&mv));
+        game.process_action(&action.unwrap(), |_, msg| {
+            assert!(!msg.contains("tutor"));
+        })
+        .unwrap();
+    }
+    notices.clear();
+    game.process_action(r#"{"action": "hint"}"#, |id, msg| {
+        notices.push((id.to_string(), msg.to_string()))
+    })
+    .unwrap();
+    assert_eq!(notices.len(), 1);
+    assert_eq!(notices[0].0, "bar");
+    assert!(notices[0].1.starts_with(r#"{"action":"tutor","#));
+
+    // Unknown players can't be tutored.
+    let params = r#"{"tutor_mode": true, "tutored_players": ["bot"]}"#;
+    assert!(<BlauAPI as GameAPI>::init(&players, Some(params)).is_err());
+}
+
+#[cfg(test)]
+fn play_to_end(game: &mut BlauAPI) -> Vec<String> {
+    let mut notices = vec![];
+    while !game.is_game_over() {
+        let mv = game.state.valid_moves()[0];
+        let action = serde_json::to_string(&MoveMessage::from_move(&mv));
+        game.process_action(&action.unwrap(), |_, msg| {
+            notices.push(msg.to_string())
+        })
+        .unwrap();
+    }
+    notices
+}
+
+#[test]
+fn end_of_game_notices() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 0),
+    ];
+    let mut game: BlauAPI = GameAPI::init(&players, None).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let notices = play_to_end(&mut game);
+    let with_action = |action: &str| -> Vec<&String> {
+        let prefix = format!(r#"{{"action":"{action}","#);
+        notices.iter().filter(|m| m.starts_with(&prefix)).collect()
+    };
+    assert_eq!(with_action("round_end").len(), game.state.round_number());
+    let end_msgs = with_action("end");
+    assert_eq!(end_msgs.len(), 1);
+    assert!(end_msgs[0].contains(r#""winner":"#));
+    assert!(end_msgs[0].contains(r#""winners":["#));
+    assert!(end_msgs[0].contains(r#""ranking":[{"player_idx":"#));
+    let game_end_msgs = with_action("game_end");
+    assert_eq!(game_end_msgs.len(), 1);
+    assert!(game_end_msgs[0].contains(r#""bonuses":["#));
+    assert_eq!(notices.last(), game_end_msgs.last().copied());
+}
+
+#[test]
+fn persisting_tutor_games() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 0),
+    ];
+    let mut game: BlauAPI =
+        GameAPI::init(&players, Some(r#"{"tutor_mode": true}"#)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    play_to_end(&mut game);
+    assert!(!game.hints.is_empty());
+    assert!(game.hints.iter().all(|h| h.player_idx == 0));
+
+    let final_state = game.final_state().unwrap();
+    let restored: BlauAPI = GameAPI::restore(&players, &final_state).unwrap();
+    assert!(restored.is_game_over());
+    assert_eq!(restored.player_scores(), game.player_scores());
+    assert_eq!(restored.moves.len(), game.moves.len());
+    assert_eq!(restored.hints.len(), game.hints.len());
+
+    // Records stored before hints were kept can still be restored.
+    let old_record = serde_json::to_string(&game.state).unwrap();
+    let restored: BlauAPI = GameAPI::restore(&players, &old_record).unwrap();
+    assert!(restored.hints.is_empty());
+}
+
+#[test]
+fn delta_updates() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": false, "delta_updates": true}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let mut notices = vec![];
+    let mv = game.state.valid_moves()[0];
+    let action = serde_json::to_string(&MoveMessage::from_move(&mv));
+    game.process_action(&action.unwrap(), |_, msg| {
+        notices.push(msg.to_string())
+    })
+    .unwrap();
+    for msg in notices
+        .iter()
+        .filter(|m| m.contains(r#""round_over":false"#))
+    {
+        assert!(msg.contains(r#""delta":{"#), "{msg}");
+        assert!(!msg.contains("game_data"), "{msg}");
+    }
+    let seqs: Vec<usize> = notices
+        .iter()
+        .filter_map(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+        .filter_map(|v| v["seq"].as_u64())
+        .map(|seq| seq as usize)
+        .collect();
+    // The bot may have moved first, but no updates are skipped.
+    let first = game.moves.len() + 1 - seqs.len();
+    assert_eq!(seqs, (first..=game.moves.len()).collect::<Vec<_>>());
+
+    notices.clear();
+    game.process_action(
+        r#"{"action": "resync", "player_id": "foo"}"#,
+        |id, msg| {
+            assert_eq!(id, "foo");
+            notices.push(msg.to_string())
+        },
+    )
+    .unwrap();
+    assert_eq!(notices.len(), 1);
+    let expected = format!(r#"{{"action":"sync","seq":{},"#, game.moves.len());
+    assert!(notices[0].starts_with(&expected), "{}", notices[0]);
+    let bad = r#"{"action": "resync", "player_id": "nobody"}"#;
+    assert!(game.process_action(bad, |_, _| {}).is_err());
+}
+
+#[test]
+fn spectating_bot_games() {
+    let players = vec![
+        PlayerInfo::ai("bot1".into(), 0),
+        PlayerInfo::ai("bot2".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": false, "spectators": ["fan"],
+                     "spectator_delay": 3}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    let mut notices = vec![];
+    game.start(1, |id, msg| {
+        assert_eq!(id, "fan");
+        notices.push(msg.to_string());
+    })
+    .unwrap();
+    assert!(game.is_game_over());
+    // Everything is delivered once the game ends, minus the RNG state.
+    let num_plays = notices.iter().filter(|m| m.contains("\"play\"")).count();
+    assert_eq!(num_plays + 1, game.moves.len());
+    assert!(notices.iter().all(|m| !m.contains("\"rng\"")));
+    assert!(notices.last().unwrap().contains("\"game_end\""));
+}
+
+#[test]
+fn delaying_spectator_notices() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+    ];
+    let params = r#"{"tutor_mode": false, "spectator_delay": 2}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let spectate = r#"{"action": "spectate", "spectator_id": "fan"}"#;
+    game.process_action(spectate, |_, _| {}).unwrap();
+    let bad = r#"{"action": "spectate", "spectator_id": "foo"}"#;
+    assert!(game.process_action(bad, |_, _| {}).is_err());
+
+    let mut seen = vec![];
+    for _ in 0..3 {
+        let mv = game.state.valid_moves()[0];
+        let action = serde_json::to_string(&MoveMessage::from_move(&mv));
+        game.process_action(&action.unwrap(), |id, msg| {
+            if id == "fan" {
+                let v: serde_json::Value = serde_json::from_str(msg).unwrap();
+                seen.push(v["seq"].as_u64().unwrap());
+            }
+        })
+        .unwrap();
+    }
+    // Spectators are two moves behind.
+    assert_eq!(seen, vec![1]);
+}
+
+#[test]
+fn resigning_ends_two_player_games() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+    ];
+    let mut game: BlauAPI = GameAPI::init(&players, None).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let bad = r#"{"action": "resign", "player_id": "nobody"}"#;
+    assert!(game.process_action(bad, |_, _| {}).is_err());
+
+    let mut notices = vec![];
+    let resign = r#"{"action": "resign", "player_id": "foo"}"#;
+    game.process_action(resign, |_, msg| notices.push(msg.to_string()))
+        .unwrap();
+    assert!(game.is_game_over());
+    let end = notices
+        .iter()
+        .find(|m| m.starts_with(r#"{"action":"end","#));
+    assert!(end.unwrap().contains(r#""winner":"bar","winners":["bar"]"#));
+    assert_eq!(game.state.ranking()[1].player_idx, 0);
+}
+
+#[test]
+fn commands_act_for_the_named_player() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+        PlayerInfo::human("baz".into()),
+    ];
+    let mut game: BlauAPI = GameAPI::init(&players, None).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    // Whoever sends it, this resigns the player it names. Checking the
+    // sender is up to the host.
+    let curr = game.state.curr_player_idx;
+    let other = (curr + 1) % 3;
+    let resign = format!(
+        r#"{{"action": "resign", "player_id": "{}"}}"#,
+        game.player_ids[other]
+    );
+    game.process_action(&resign, |_, _| {}).unwrap();
+    assert!(game.state.players[other].has_resigned());
+    assert!(!game.state.players[curr].has_resigned());
+}
+
+#[test]
+fn abandoned_seats_are_taken_over() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+    ];
+    let params = r#"{"tutor_mode": false, "takeover_level": 0}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let abandon = r#"{"action": "abandon", "player_id": "foo"}"#;
+    game.process_action(abandon, |_, _| {}).unwrap();
+    assert!(game.agents[0].is_some());
+    assert!(!game.is_game_over());
+    // The bot plays for "foo" until the game ends.
+    play_to_end(&mut game);
+    assert!(game.state.ranking().iter().all(|s| !s.resigned));
+}
+
+#[test]
+fn leaving_out_of_turn_keeps_the_hint() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+        PlayerInfo::human("baz".into()),
+    ];
+    let params = r#"{"tutor_mode": true, "takeover_level": 0}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    assert_eq!(game.hints.len(), 1);
+    let curr = game.state.curr_player_idx;
+    let others: Vec<&str> = (0..3)
+        .filter(|&idx| idx != curr)
+        .map(|idx| game.player_ids[idx].as_str())
+        .collect();
+    let actions = [
+        format!(r#"{{"action": "abandon", "player_id": "{}"}}"#, others[0]),
+        format!(r#"{{"action": "resign", "player_id": "{}"}}"#, others[1]),
+    ];
+    for action in actions {
+        let mut notices = vec![];
+        game.process_action(&action, |_, msg| notices.push(msg.to_string()))
+            .unwrap();
+        assert!(notices.iter().all(|m| !m.contains(r#""action":"tutor""#)));
+        assert_eq!(game.hints.len(), 1);
+        assert_eq!(game.state.curr_player_idx, curr);
+    }
+}
+
+#[test]
+fn timing_out_moves_to_the_floor() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+    ];
+    let params = r#"{"tutor_mode": false,
+                     "clock": {"total_ms": 60000, "increment_ms": 1000}}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let start = game.turn_started;
+    let first = game.state.curr_player_idx;
+    let no_timeout = game.check_timeouts(start, |_, _| {}).unwrap();
+    assert!(!no_timeout);
+
+    let mut notices = vec![];
+    let later = start + Duration::from_secs(61);
+    let timed_out = game
+        .check_timeouts(later, |_, msg| notices.push(msg.to_string()))
+        .unwrap();
+    assert!(timed_out);
+    let timeout = format!(r#"{{"action":"timeout","player_idx":{first}}}"#);
+    assert_eq!(notices[0], timeout);
+    assert_eq!(game.moves.len(), 1);
+    assert_eq!(game.moves[0].working, 5);
+    // The player who timed out still gets the increment.
+    assert_eq!(game.clocks[first], Duration::from_secs(1));
+    assert_ne!(game.state.curr_player_idx, first);
+    // The next player's clock started when the timeout was handled.
+    assert!(!game.check_timeouts(later, |_, _| {}).unwrap());
+}
+
+// Thinks for a while before playing greedily.
+#[cfg(test)]
+struct SlowAgent;
+
+#[cfg(test)]
+impl Agent for SlowAgent {
+    fn choose_action(&self, game: &GameState) -> player_move::Move {
+        std::thread::sleep(Duration::from_millis(100));
+        create_agent(0).choose_action(game)
+    }
+}
+
+#[test]
+fn agents_think_on_their_own_clock() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": false, "clock": {"total_ms": 60000}}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    game.agents[1] = Some(Box::new(SlowAgent));
+    assert_eq!(game.state.curr_player_idx, 0);
+    let mv = create_agent(0).choose_action(&game.state);
+    let mv = serde_json::to_string(&MoveMessage::from_move(&mv)).unwrap();
+    game.process_action(&mv, |_, _| {}).unwrap();
+    assert_eq!(game.state.curr_player_idx, 0);
+    // The bot's thinking came off its own clock, not the human's.
+    assert!(game.clocks[1] <= Duration::from_millis(59900));
+    assert!(game.turn_started.elapsed() < Duration::from_millis(100));
+    let later = game.turn_started + Duration::from_millis(59950);
+    assert!(!game.check_timeouts(later, |_, _| {}).unwrap());
+}
+
+#[test]
+fn rejecting_bad_player_counts() {
+    let players = vec![PlayerInfo::human("foo".into())];
+    assert!(<BlauAPI as GameAPI>::init(&players, None).is_err());
+}
+
+#[test]
+fn choosing_rules() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::human("bar".into()),
+    ];
+    let params = r#"{"tutor_mode": false, "rules": "quick"}"#;
+    let game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    assert_eq!(game.state.rules().max_rounds, Some(3));
+
+    let params = r#"{"tutor_mode": false, "rules": {"row_bonus": 5}}"#;
+    let game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    assert_eq!(game.state.rules().row_bonus, 5);
+    assert_eq!(game.state.rules().column_bonus, 7);
+
+    let params = r#"{"tutor_mode": false, "rules": "bogus"}"#;
+    assert!(<BlauAPI as GameAPI>::init(&players, Some(params)).is_err());
+}
+
+#[test]
+fn placing_tiles_on_a_free_wall() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": false, "rules": "gray"}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let mut prompts = 0;
+    while !game.is_game_over() {
+        let mut notices = vec![];
+        let action = match game.state.pending_placement() {
+            Some((idx, row)) => {
+                assert_eq!(idx, 0);
+                let column = game.state.players[0].placement_options(row)[0];
+                // Moves aren't accepted until the wall is placed.
+                let mv = r#"{"color":0,"factory":1,"working":5}"#;
+                assert!(game.process_action(mv, |_, _| {}).is_err());
+                format!(
+                    r#"{{"action":"place","player_id":"foo","row":{row},"column":{column}}}"#
+                )
+            }
+            None => {
+                let mv = create_agent(0).choose_action(&game.state);
+                serde_json::to_string(&MoveMessage::from_move(&mv)).unwrap()
+            }
+        };
+        game.process_action(&action, |_, msg| notices.push(msg.to_string()))
+            .unwrap();
+        prompts += notices
+            .iter()
+            .filter(|m| m.starts_with(r#"{"action":"place","#))
+            .count();
+    }
+    assert!(prompts > 0);
+}
+
+#[test]
+fn timing_out_wall_placements() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": false, "rules": "gray",
+                     "clock": {"total_ms": 60000}}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    // Play until the human has to choose a wall column.
+    while game.state.pending_placement().is_none() {
+        let mv = create_agent(0).choose_action(&game.state);
+        let mv = serde_json::to_string(&MoveMessage::from_move(&mv)).unwrap();
+        game.process_action(&mv, |_, _| {}).unwrap();
+    }
+    let (idx, row) = game.state.pending_placement().unwrap();
+    assert_eq!(idx, 0);
+    let expected = game.state.players[0].best_placement(row).unwrap();
+    let later = game.turn_started + Duration::from_secs(61);
+    let mut notices = vec![];
+    let timed_out = game
+        .check_timeouts(later, |_, msg| notices.push(msg.to_string()))
+        .unwrap();
+    assert!(timed_out);
+    assert_eq!(notices[0], r#"{"action":"timeout","player_idx":0}"#);
+    let placed = format!(
+        r#"{{"action":"placed","player_idx":0,"row":{row},"column":{expected}}}"#
+    );
+    assert_eq!(notices[1], placed);
+    // The round was scored and play went on.
+    assert!(game.state.pending_placement().is_none());
+    assert!(
+        notices
+            .iter()
+            .any(|m| m.starts_with(r#"{"action":"round_end","#))
+    );
+}
+
+#[test]
+fn wild_tiles_in_move_messages() {
+    let msg: MoveMessage = serde_json::from_str(
+        r#"{"color":5,"factory":1,"working":0,"wilds":2}"#,
+    )
+    .unwrap();
+    let mv = msg.to_move().unwrap();
+    assert_eq!(mv.color, Color::Yellow);
+    assert_eq!(mv.num_wilds, 2);
+    let json = serde_json::to_string(&MoveMessage::from_move(&mv)).unwrap();
+    assert!(json.ends_with(r#""wilds":2}"#));
+
+    // Moves without wild tiles keep the old format.
+    let mv = player_move::Move { num_wilds: 0, ..mv };
+    let json = serde_json::to_string(&MoveMessage::from_move(&mv)).unwrap();
+    assert_eq!(json, r#"{"color":5,"factory":1,"working":0}"#);
+}
+
+#[test]
+fn team_play() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 0),
+        PlayerInfo::human("bar".into()),
+        PlayerInfo::ai("bat".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": true, "hints_on_demand": true,
+        "partner_hints": true, "rules": {"team_play": true}}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    let player_id = game.current_player_id().to_string();
+    let partner_id = if player_id == "foo" { "bar" } else { "foo" };
+    let mut notices = vec![];
+    game.process_action(r#"{"action": "hint"}"#, |id, msg| {
+        notices.push((id.to_string(), msg.to_string()))
+    })
+    .unwrap();
+    assert_eq!(notices.len(), 2);
+    assert_eq!(notices[0].0, player_id);
+    assert_eq!(notices[1].0, partner_id);
+    assert!(notices[1].1.starts_with(r#"{"action":"partner_hint","#));
+
+    let notices = play_to_end(&mut game);
+    // Partners share the win, so there is no sole winner.
+    let end = notices
+        .iter()
+        .find(|m| m.starts_with(r#"{"action":"end","#))
+        .unwrap();
+    assert!(end.contains(r#""winner":null"#));
+    let scores = game.player_scores();
+    assert_eq!(scores[0], scores[2]);
+    assert_eq!(scores[1], scores[3]);
+    let winners = game.state.winners();
+    assert!(
+        winners == vec![0, 2] || winners == vec![1, 3] || winners.len() == 4
+    );
+
+    let params = r#"{"tutor_mode": false, "rules": {"team_play": true}}"#;
+    assert!(<BlauAPI as GameAPI>::init(&players[..3], Some(params)).is_err());
+}
+
+#[test]
+fn handicaps() {
+    let players = vec![
+        PlayerInfo::human("foo".into()),
+        PlayerInfo::ai("bot".into(), 2),
+    ];
+    let params = r#"{"tutor_mode": false, "handicaps": {
+        "foo": {"start_score": 8, "wall_tiles": [[0, 0]]},
+        "bot": {"search_budget": 1, "blunder_percent": 50}}}"#;
+    let mut game: BlauAPI = GameAPI::init(&players, Some(params)).unwrap();
+    game.start(1, |_, _| {}).unwrap();
+    assert!(game.state.players[0].score() >= 8);
+    let state = game.player_view("foo").unwrap();
+    assert!(state.contains(r#""blunder_percent":50"#));
+    let notices = play_to_end(&mut game);
+    let end = notices
+        .iter()
+        .find(|m| m.starts_with(r#"{"action":"end","#))
+        .unwrap();
+    assert!(end.contains(r#""handicap":{"start_score":8,"#));
+
+    // Bots with the same seed make the same blunders.
+    let handicap = Handicap {
+        blunder_percent: 50,
+        ..Handicap::default()
+    };
+    let bots: Vec<_> = (0..2)
+        .map(|_| create_handicapped_agent(0, &handicap, 9))
+        .collect();
+    let mut state = GameState::new(&["ann", "bob"], &mut rand::rng()).unwrap();
+    state.start_round();
+    for _ in 0..10 {
+        assert_eq!(
+            bots[0].choose_action(&state),
+            bots[1].choose_action(&state)
+        );
+    }
+
+    // Blunders are drawn from the game seed.
+    let bots = vec![
+        PlayerInfo::ai("ann".into(), 0),
+        PlayerInfo::ai("bob".into(), 0),
+    ];
+    let params = r#"{"tutor_mode": false, "seed": 9, "handicaps": {
+        "ann": {"blunder_percent": 50}, "bob": {"blunder_percent": 50}}}"#;
+    let moves: Vec<String> = (0..2)
+        .map(|_| {
+            let mut game: BlauAPI = GameAPI::init(&bots, Some(params)).unwrap();
+            game.start(1, |_, _| {}).unwrap();
+            assert!(game.is_game_over());
+            serde_json::to_string(&game.moves).unwrap()
+        })
+        .collect();
+    assert_eq!(moves[0], moves[1]);
+
+    for bad in [
+        r#"{"tutor_mode": false, "handicaps": {"baz": {}}}"#,
+        r#"{"tutor_mode": false, "handicaps": {"foo": {"search_budget": 2}}}"#,
+    ] {
+        assert!(<BlauAPI as GameAPI>::init(&players, Some(bad)).is_err());
+    }
+}
@@ -0,0 +1,567 @@
+use crate::agent::{Agent, RandomAgent, create_handicapped_agent};
+use crate::game_state::GameState;
+use crate::rules::{Handicap, RuleSet};
+use crate::stats::{Alternative, Elo, Rating, mean_interval, wilcoxon};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A named bot taking part in a tournament.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AgentSpec {
+    pub name: String,
+    // Bot level, as used by `create_agent`. None plays random moves.
+    pub level: Option<usize>,
+    #[serde(default, skip_serializing_if = "Handicap::is_none")]
+    pub handicap: Handicap,
+}
+
+impl AgentSpec {
+    /// Parses a spec like `name=kind[,option=value...]`, where the kind is
+    /// `random`, `greedy`, `planner`, `deep` or a bot level, and the options
+    /// are `budget`, `blunder` and `start`, as in a player handicap.
+    pub fn parse(spec: &str) -> Result<AgentSpec, String> {
+        let (name, rest) = spec
+            .split_once('=')
+            .ok_or_else(|| format!("Expected name=kind: {}", spec))?;
+        let mut parts = rest.split(',');
+        let level = match parts.next().unwrap_or_default() {
+            "random" => None,
+            "greedy" => Some(0),
+            "planner" => Some(1),
+            "deep" => Some(2),
+            kind => Some(
+                kind.parse()
+                    .map_err(|_| format!("Unknown agent kind: {}", kind))?,
+            ),
+        };
+        let mut handicap = Handicap::default();
+        for option in parts {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| format!("Expected option=value: {}", option))?;
+            let bad_value = |_| format!("Bad value for {}: {}", key, value);
+            match key {
+                "budget" => {
+                    handicap.search_budget =
+                        Some(value.parse().map_err(bad_value)?)
+                }
+                "blunder" => {
+                    handicap.blunder_percent =
+                        value.parse().map_err(bad_value)?
+                }
+                "start" => {
+                    handicap.start_score = value.parse().map_err(bad_value)?
+                }
+                _ => return Err(format!("Unknown agent option: {}", key)),
+            }
+        }
+        if name.is_empty() {
+            return Err(format!("Missing agent name: {}", spec));
+        }
+        Ok(AgentSpec {
+            name: name.to_string(),
+            level,
+            handicap,
+        })
+    }
+
+    /// Creates the agent. Any random choices it makes are drawn from `seed`.
+    pub fn create_agent(&self, seed: u64) -> Box<dyn Agent + Send> {
+        match self.level {
+            Some(level) => {
+                create_handicapped_agent(level, &self.handicap, seed)
+            }
+            // Random agents blunder on every move already.
+            None => Box::new(RandomAgent::new(seed)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    // Every group of agents plays at a table together.
+    RoundRobin,
+    // The first agent plays against every group of the others.
+    Gauntlet,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tournament {
+    pub agents: Vec<AgentSpec>,
+    pub format: Format,
+    // Number of players in each game.
+    pub table_size: usize,
+    // Number of deals each table plays. Every deal is played once from each
+    // seat rotation, so that no agent is luckier with the tiles.
+    pub deals: usize,
+    pub seed: u64,
+    pub rules: RuleSet,
+}
+
+/// One scheduled game: agent indices in seat order, and the deal's seed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pairing {
+    pub seats: Vec<usize>,
+    pub seed: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GameOutcome {
+    pub seats: Vec<usize>,
+    pub seed: u64,
+    // Scores and ranks in seat order.
+    pub scores: Vec<i32>,
+    pub ranks: Vec<usize>,
+    pub rounds: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentSummary {
+    pub name: String,
+    pub games: usize,
+    pub mean_score: f64,
+    pub std_score: f64,
+    pub min_score: i32,
+    pub max_score: i32,
+    // 95% confidence interval for the mean score.
+    pub score_interval: (f64, f64),
+    pub mean_rank: f64,
+    // Number of games finished at each rank, starting from 1st place.
+    pub rank_counts: Vec<usize>,
+    // Final Elo and skill ratings, from the games in schedule order.
+    pub elo: f64,
+    pub rating: Rating,
+}
+
+/// How the first agent did against another, in the games they shared.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Comparison {
+    pub agent: String,
+    pub opponent: String,
+    pub games: usize,
+    pub mean_diff: f64,
+    // 95% confidence interval for the mean score difference.
+    pub diff_interval: (f64, f64),
+    // Wilcoxon p-values for the agent scoring more, and for it scoring less.
+    // None if every game was a tie.
+    pub p_better: Option<f64>,
+    pub p_worse: Option<f64>,
+}
+
+impl Comparison {
+    /// True if the agent is significantly worse than its opponent.
+    pub fn is_regression(&self, alpha: f64) -> bool {
+        self.p_worse.is_some_and(|p| p < alpha)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TournamentResult {
+    pub tournament: Tournament,
+    pub summaries: Vec<AgentSummary>,
+    // The first agent against each of the others.
+    pub comparisons: Vec<Comparison>,
+    pub games: Vec<GameOutcome>,
+}
+
+impl Tournament {
+    /// Lists every game to play, in a fixed order.
+    pub fn schedule(&self) -> Result<Vec<Pairing>, String> {
+        let n = self.agents.len();
+        let k = self.table_size;
+        if k < 2 {
+            return Err("Tables need at least two players".to_string());
+        }
+        if n < k {
+            return Err(format!("Need at least {} agents, got {}", k, n));
+        }
+        for (i, spec) in self.agents.iter().enumerate() {
+            if self.agents[..i].iter().any(|a| a.name == spec.name) {
+                return Err(format!("Duplicate agent name: {}", spec.name));
+            }
+        }
+        let tables: Vec<Vec<usize>> = match self.format {
+            Format::RoundRobin => combinations(&(0..n).collect::<Vec<_>>(), k),
+            Format::Gauntlet => {
+                combinations(&(1..n).collect::<Vec<_>>(), k - 1)
+                    .into_iter()
+                    .map(|others| [vec![0], others].concat())
+                    .collect()
+            }
+        };
+        let mut pairings = vec![];
+        for table in &tables {
+            for deal in 0..self.deals {
+                for rotation in 0..k {
+                    let mut seats = table.clone();
+                    seats.rotate_left(rotation);
+                    pairings.push(Pairing {
+                        seats,
+                        seed: self.seed.wrapping_add(deal as u64),
+                    });
+                }
+            }
+        }
+        Ok(pairings)
+    }
+
+    /// Plays every scheduled game, spread over `num_threads` threads.
+    pub fn run(&self, num_threads: usize) -> Result<TournamentResult, String> {
+        let pairings = self.schedule()?;
+        let next = AtomicUsize::new(0);
+        let mut results: Vec<(usize, Result<GameOutcome, String>)> =
+            std::thread::scope(|s| {
+                let workers: Vec<_> = (0..num_threads.max(1))
+                    .map(|_| {
+                        s.spawn(|| {
+                            let mut done = vec![];
+                            loop {
+                                let idx = next.fetch_add(1, Ordering::Relaxed);
+                                let Some(p) = pairings.get(idx) else {
+                                    return done;
+                                };
+                                done.push((idx, self.play(p)));
+                            }
+                        })
+                    })
+                    .collect();
+                workers
+                    .into_iter()
+                    .flat_map(|w| w.join().expect("Arena thread panicked"))
+                    .collect()
+            });
+        results.sort_by_key(|(idx, _)| *idx);
+        let games = results
+            .into_iter()
+            .map(|(_, outcome)| outcome)
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(TournamentResult {
+            summaries: self.summarize(&games),
+            comparisons: (1..self.agents.len())
+                .filter_map(|b| self.compare(&games, 0, b))
+                .collect(),
+            tournament: self.clone(),
+            games,
+        })
+    }
+
+    /// Plays a single game to the end.
+    pub fn play(&self, pairing: &Pairing) -> Result<GameOutcome, String> {
+        let names: Vec<&str> = pairing
+            .seats
+            .iter()
+            .map(|&a| self.agents[a].name.as_str())
+            .collect();
+        let seed = ChaCha8Rng::seed_from_u64(pairing.seed).random();
+        let rules = Arc::new(self.rules.clone());
+        let mut game = GameState::from_seed(&names, rules, seed)?;
+        // Agents draw from their own stream of the deal's seed.
+        let mut agent_rng = ChaCha8Rng::seed_from_u64(pairing.seed);
+        agent_rng.set_stream(1);
+        let mut agents = Vec::with_capacity(names.len());
+        for (idx, &a) in pairing.seats.iter().enumerate() {
+            game.set_handicap(idx, self.agents[a].handicap.clone())?;
+            agents.push(self.agents[a].create_agent(agent_rng.random()));
+        }
+        game.start_round();
+        loop {
+            let m = agents[game.curr_player_idx].choose_action(&game);
+            if !game.take_turn(&m)? {
+                continue;
+            }
+            while let Some((idx, row)) = game.pending_placement() {
+                let column = agents[idx]
+                    .choose_placement(&game, idx, row)
+                    .ok_or("No column to place in")?;
+                game.place_tile(idx, row, column)?;
+            }
+            if game.finish_round()? {
+                break;
+            }
+            game.start_round();
+        }
+        let mut ranks = vec![0; names.len()];
+        for standing in game.ranking() {
+            ranks[standing.player_idx] = standing.rank;
+        }
+        Ok(GameOutcome {
+            seats: pairing.seats.clone(),
+            seed: pairing.seed,
+            scores: (0..names.len()).map(|idx| game.team_score(idx)).collect(),
+            ranks,
+            rounds: game.round_number(),
+        })
+    }
+
+    /// Collects each agent's score and rank distribution.
+    pub fn summarize(&self, games: &[GameOutcome]) -> Vec<AgentSummary> {
+        let mut elo = Elo::new(self.agents.len());
+        let mut ratings = vec![Rating::default(); self.agents.len()];
+        for game in games {
+            elo.update(&game.seats, &game.ranks);
+            Rating::update(&mut ratings, &game.seats, &game.ranks);
+        }
+        self.agents
+            .iter()
+            .enumerate()
+            .map(|(agent, spec)| {
+                let mut scores = vec![];
+                let mut rank_counts = vec![0; self.table_size];
+                for game in games {
+                    for (seat, &a) in game.seats.iter().enumerate() {
+                        if a == agent {
+                            scores.push(game.scores[seat]);
+                            rank_counts[game.ranks[seat] - 1] += 1;
+                        }
+                    }
+                }
+                let n = scores.len().max(1) as f64;
+                let mean = scores.iter().sum::<i32>() as f64 / n;
+                let var = scores
+                    .iter()
+                    .map(|&s| (s as f64 - mean).powi(2))
+                    .sum::<f64>()
+                    / n;
+                let rank_sum: usize = rank_counts
+                    .iter()
+                    .enumerate()
+                    .map(|(r, c)| (r + 1) * c)
+                    .sum();
+                let samples: Vec<f64> =
+                    scores.iter().map(|&s| s as f64).collect();
+                AgentSummary {
+                    name: spec.name.clone(),
+                    games: scores.len(),
+                    mean_score: mean,
+                    std_score: var.sqrt(),
+                    min_score: scores.iter().copied().min().unwrap_or(0),
+                    max_score: scores.iter().copied().max().unwrap_or(0),
+                    score_interval: mean_interval(&samples, 0.95),
+                    mean_rank: rank_sum as f64 / n,
+                    rank_counts,
+                    elo: elo.ratings[agent],
+                    rating: ratings[agent],
+                }
+            })
+            .collect()
+    }
+
+    /// Compares agents `a` and `b` by their score differences in the games
+    /// where both played. Returns None if they never met.
+    pub fn compare(
+        &self,
+        games: &[GameOutcome],
+        a: usize,
+        b: usize,
+    ) -> Option<Comparison> {
+        let diffs: Vec<f64> = games
+            .iter()
+            .filter_map(|game| {
+                let seat_a = game.seats.iter().position(|&x| x == a)?;
+                let seat_b = game.seats.iter().position(|&x| x == b)?;
+                Some((game.scores[seat_a] - game.scores[seat_b]) as f64)
+            })
+            .collect();
+        if diffs.is_empty() {
+            return None;
+        }
+        Some(Comparison {
+            agent: self.agents[a].name.clone(),
+            opponent: self.agents[b].name.clone(),
+            games: diffs.len(),
+            mean_diff: diffs.iter().sum::<f64>() / diffs.len() as f64,
+            diff_interval: mean_interval(&diffs, 0.95),
+            p_better: wilcoxon(&diffs, Alternative::Greater),
+            p_worse: wilcoxon(&diffs, Alternative::Less),
+        })
+    }
+}
+
+impl TournamentResult {
+    /// Formats the agent summaries as CSV, with one column per rank.
+    pub fn summary_csv(&self) -> String {
+        let ranks = self.tournament.table_size;
+        let mut out = String::from(
+            "agent,games,mean_score,std_score,min_score,max_score,mean_rank,elo",
+        );
+        for rank in 1..=ranks {
+            out += &format!(",rank_{}", rank);
+        }
+        out.push('\n');
+        for s in &self.summaries {
+            out += &format!(
+                "{},{},{:.3},{:.3},{},{},{:.3},{:.1}",
+                s.name,
+                s.games,
+                s.mean_score,
+                s.std_score,
+                s.min_score,
+                s.max_score,
+                s.mean_rank,
+                s.elo
+            );
+            for count in &s.rank_counts {
+                out += &format!(",{}", count);
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    /// Formats every game as CSV, with one line per seat.
+    pub fn games_csv(&self) -> String {
+        let mut out = String::from("game,seed,seat,agent,score,rank\n");
+        for (idx, game) in self.games.iter().enumerate() {
+            for (seat, &a) in game.seats.iter().enumerate() {
+                out += &format!(
+                    "{},{},{},{},{},{}\n",
+                    idx,
+                    game.seed,
+                    seat,
+                    self.tournament.agents[a].name,
+                    game.scores[seat],
+                    game.ranks[seat]
+                );
+            }
+        }
+        out
+    }
+}
+
+// All ways to pick `k` items from `items`, keeping their order.
+fn combinations(items: &[usize], k: usize) -> Vec<Vec<usize>> {
+    if k == 0 {
+        return vec![vec![]];
+    }
+    let mut out = vec![];
+    for (i, &first) in items.iter().enumerate() {
+        for mut rest in combinations(&items[i + 1..], k - 1) {
+            rest.insert(0, first);
+            out.push(rest);
+        }
+    }
+    out
+}
+
+#[test]
+fn parsing_agent_specs() {
+    let spec = AgentSpec::parse("test=planner,budget=3,blunder=10").unwrap();
+    assert_eq!(spec.name, "test");
+    assert_eq!(spec.level, Some(1));
+    assert_eq!(spec.handicap.search_budget, Some(3));
+    assert_eq!(spec.handicap.blunder_percent, 10);
+    assert_eq!(AgentSpec::parse("r=random").unwrap().level, None);
+    assert_eq!(AgentSpec::parse("x=2").unwrap().level, Some(2));
+    assert!(AgentSpec::parse("greedy").is_err());
+    assert!(AgentSpec::parse("x=smart").is_err());
+    assert!(AgentSpec::parse("x=greedy,budget").is_err());
+    assert!(AgentSpec::parse("x=greedy,speed=3").is_err());
+    assert!(AgentSpec::parse("=greedy").is_err());
+}
+
+#[cfg(test)]
+fn greedy_tournament(format: Format, num_agents: usize) -> Tournament {
+    Tournament {
+        agents: (0..num_agents)
+            .map(|i| AgentSpec::parse(&format!("g{}=greedy", i)).unwrap())
+            .collect(),
+        format,
+        table_size: 2,
+        deals: 1,
+        seed: 5,
+        rules: RuleSet::default(),
+    }
+}
+
+#[test]
+fn replaying_random_agents() {
+    let t = Tournament {
+        agents: vec![
+            AgentSpec::parse("r=random").unwrap(),
+            AgentSpec::parse("b=greedy,blunder=30").unwrap(),
+        ],
+        deals: 2,
+        ..greedy_tournament(Format::RoundRobin, 2)
+    };
+    let games = |threads| {
+        let result = t.run(threads).unwrap();
+        serde_json::to_string(&result.games).unwrap()
+    };
+    // Every choice comes from the tournament seed.
+    assert_eq!(games(1), games(2));
+}
+
+#[test]
+fn scheduling_tournaments() {
+    let t = greedy_tournament(Format::RoundRobin, 3);
+    let pairings = t.schedule().unwrap();
+    // Three tables, each playing one deal from both seat rotations.
+    assert_eq!(pairings.len(), 6);
+    assert_eq!(pairings[0].seats, vec![0, 1]);
+    assert_eq!(pairings[1].seats, vec![1, 0]);
+    assert_eq!(pairings[0].seed, pairings[1].seed);
+
+    let t = greedy_tournament(Format::Gauntlet, 3);
+    let pairings = t.schedule().unwrap();
+    assert_eq!(pairings.len(), 4);
+    assert!(pairings.iter().all(|p| p.seats.contains(&0)));
+
+    let t = greedy_tournament(Format::Gauntlet, 1);
+    assert!(t.schedule().is_err());
+}
+
+#[test]
+fn comparing_agents() {
+    let mut t = greedy_tournament(Format::Gauntlet, 3);
+    t.agents[0] = AgentSpec::parse("r=random").unwrap();
+    let games: Vec<GameOutcome> = (0..12)
+        .map(|i| GameOutcome {
+            seats: vec![0, 1 + i % 2],
+            seed: i as u64,
+            scores: vec![i as i32 % 3, 10 + i as i32],
+            ranks: vec![2, 1],
+            rounds: 5,
+        })
+        .collect();
+    let c = t.compare(&games, 0, 1).unwrap();
+    assert_eq!(c.games, 6);
+    assert!(c.is_regression(0.05));
+    assert!(c.diff_interval.1 < 0.0);
+    assert!(t.compare(&games, 1, 2).is_none());
+
+    let summaries = t.summarize(&games);
+    assert!(summaries[0].elo < 1500.0);
+    assert!(summaries[1].rating.mu > summaries[0].rating.mu);
+}
+
+#[test]
+fn running_tournaments() {
+    let t = greedy_tournament(Format::RoundRobin, 2);
+    let result = t.run(2).unwrap();
+    assert_eq!(result.games.len(), 2);
+    // Identical agents play the same game from either seat of a deal.
+    assert_eq!(result.games[0].scores, result.games[1].scores);
+    assert_eq!(result.games[0].seats, vec![0, 1]);
+    assert_eq!(result.games[1].seats, vec![1, 0]);
+    for s in &result.summaries {
+        assert_eq!(s.games, 2);
+        assert_eq!(s.rank_counts.iter().sum::<usize>(), 2);
+    }
+    // Running again gives the same games.
+    let again = t.run(1).unwrap();
+    assert_eq!(again.games[0].scores, result.games[0].scores);
+
+    // Equal agents aren't significantly different.
+    assert_eq!(result.comparisons.len(), 1);
+    assert_eq!(result.comparisons[0].mean_diff, 0.0);
+    assert!(!result.comparisons[0].is_regression(0.05));
+
+    let csv = result.summary_csv();
+    assert!(csv.starts_with("agent,games,"));
+    assert_eq!(csv.lines().count(), 3);
+    assert_eq!(result.games_csv().lines().count(), 5);
+}
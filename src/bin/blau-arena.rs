@@ -0,0 +1,146 @@
+//! Plays tournaments between Blau bots and reports how each one did.
+//!
+//! Example: a planner against two greedy bots, over 50 deals per table.
+//!
+//!     blau-arena --agent test=planner --agent a=greedy --agent b=greedy \
+//!         --gauntlet --deals 50 --format csv
+use blau::arena::{AgentSpec, Format, Tournament};
+use blau::rules::RuleSet;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+Usage: blau-arena --agent NAME=KIND[,OPTION=VALUE...] --agent ... [options]
+
+Agent kinds are random, greedy, planner, deep, or a bot level. Options are
+budget=N (moves to look ahead from), blunder=PERCENT and start=POINTS.
+
+Options:
+  --gauntlet        The first agent plays every group of the others,
+                    instead of a round-robin between all agents.
+  --players N       Players in each game (default 2).
+  --deals N         Deals played by each table, once per seat rotation
+                    (default 10).
+  --seed N          Seed for the first deal (default random).
+  --threads N       Games to play at once (default: one per CPU).
+  --rules NAME      Rule preset, or a JSON file with a rule set.
+  --format FORMAT   json (default), csv, or games-csv for one line per seat
+                    of every game.
+  --fail-on-regression
+                    Exit with an error if the first agent scores
+                    significantly less than any other (p < 0.05).";
+
+struct Args {
+    tournament: Tournament,
+    threads: usize,
+    format: String,
+    fail_on_regression: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut tournament = Tournament {
+        agents: vec![],
+        format: Format::RoundRobin,
+        table_size: 2,
+        deals: 10,
+        seed: rand::random(),
+        rules: RuleSet::default(),
+    };
+    let mut threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let mut format = "json".to_string();
+    let mut fail_on_regression = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--gauntlet" => {
+                tournament.format = Format::Gauntlet;
+                continue;
+            }
+            "--fail-on-regression" => {
+                fail_on_regression = true;
+                continue;
+            }
+            _ => {}
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        let number = || {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("Bad value for {}: {}", arg, value))
+        };
+        match arg.as_str() {
+            "--agent" => tournament.agents.push(AgentSpec::parse(&value)?),
+            "--players" => tournament.table_size = number()?,
+            "--deals" => tournament.deals = number()?,
+            "--seed" => tournament.seed = number()? as u64,
+            "--threads" => threads = number()?,
+            "--rules" => tournament.rules = load_rules(&value)?,
+            "--format" => match value.as_str() {
+                "json" | "csv" | "games-csv" => format = value,
+                _ => return Err(format!("Unknown format: {}", value)),
+            },
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    Ok(Args {
+        tournament,
+        threads,
+        format,
+        fail_on_regression,
+    })
+}
+
+fn load_rules(name: &str) -> Result<RuleSet, String> {
+    if !name.ends_with(".json") {
+        return RuleSet::preset(name);
+    }
+    let text = std::fs::read_to_string(name).map_err(|e| e.to_string())?;
+    serde_json::from_str(&text).map_err(|e| e.to_string())
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.is_empty() || args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+    let args = match parse_args(args.into_iter()) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let result = match args.tournament.run(args.threads) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("{}", e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match args.format.as_str() {
+        "csv" => print!("{}", result.summary_csv()),
+        "games-csv" => print!("{}", result.games_csv()),
+        _ => println!("{}", serde_json::to_string_pretty(&result).unwrap()),
+    }
+    let regressions: Vec<_> = result
+        .comparisons
+        .iter()
+        .filter(|c| c.is_regression(0.05))
+        .collect();
+    for c in &regressions {
+        eprintln!(
+            "{} scores {:.2} less than {} (p = {:.4})",
+            c.agent,
+            -c.mean_diff,
+            c.opponent,
+            c.p_worse.unwrap_or(1.0)
+        );
+    }
+    if args.fail_on_regression && !regressions.is_empty() {
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,7 @@
+//! Serves the built-in agents over the engine protocol on stdin and stdout,
+//! so that other programs can play against them. See `blau::engine`.
+use std::io::{stdin, stdout};
+
+fn main() -> std::io::Result<()> {
+    blau::engine::run_engine(stdin().lock(), stdout().lock())
+}
@@ -0,0 +1,429 @@
+//! Plays a game of Blau in the terminal, against bots.
+//!
+//!     blau-play --bots 2 --level 1
+use blau::agent::{Agent, create_agent};
+use blau::game_state::GameState;
+use blau::player_move::Move;
+use blau::render::Painter;
+use blau::rules::RuleSet;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+const USAGE: &str = "\
+Usage: blau-play [options]
+
+Options:
+  --name NAME     Your name (default \"you\").
+  --bots N        Number of bots to play against (default 1).
+  --level N       Bot level: 0 is greedy, 1 plans ahead, 2 plans further
+                  (default 1).
+  --rules NAME    Rule preset: standard, quick or gray.
+  --seed N        Seed for the tile draws (default random).
+  --no-color      Don't use ANSI colors.";
+
+const HELP: &str = "\
+Enter the number of a choice of tiles and then the row to put them in, for
+example \"3 2\" or \"3 F\" for the floor. Other commands:
+  h   ask the tutor for a hint
+  u   undo your last move
+  q   quit";
+
+struct Options {
+    name: String,
+    bots: usize,
+    level: usize,
+    rules: RuleSet,
+    seed: Option<u64>,
+    ansi: bool,
+}
+
+fn parse_args(
+    mut args: impl Iterator<Item = String>,
+) -> Result<Options, String> {
+    let mut opts = Options {
+        name: "you".to_string(),
+        bots: 1,
+        level: 1,
+        rules: RuleSet::default(),
+        seed: None,
+        ansi: true,
+    };
+    while let Some(arg) = args.next() {
+        if arg == "--no-color" {
+            opts.ansi = false;
+            continue;
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", arg))?;
+        let number = || {
+            value
+                .parse::<u64>()
+                .map_err(|_| format!("Bad value for {}: {}", arg, value))
+        };
+        match arg.as_str() {
+            "--name" => opts.name = value.clone(),
+            "--bots" => opts.bots = number()? as usize,
+            "--level" => opts.level = number()? as usize,
+            "--rules" => opts.rules = RuleSet::preset(&value)?,
+            "--seed" => opts.seed = Some(number()?),
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+    Ok(opts)
+}
+
+// Describes the tiles a move takes, and where from.
+fn describe_take(game: &GameState, m: &Move) -> String {
+    let source = if m.is_from_center() {
+        "the center".to_string()
+    } else {
+        format!("factory {}", m.factory_idx)
+    };
+    let wilds = if m.num_wilds > 0 {
+        format!(" and {} wild", m.num_wilds)
+    } else {
+        String::new()
+    };
+    let n = game.num_tiles_taken(m).unwrap_or(0) - m.num_wilds;
+    format!("{} {:?}{} from {}", n, m.color, wilds, source)
+}
+
+fn row_name(game: &GameState, row: usize) -> String {
+    if row == game.floor_row() {
+        "F".to_string()
+    } else {
+        (row + 1).to_string()
+    }
+}
+
+fn describe(game: &GameState, m: &Move) -> String {
+    let target = if m.working_row == game.floor_row() {
+        "the floor".to_string()
+    } else {
+        format!("row {}", m.working_row + 1)
+    };
+    format!("{} to {}", describe_take(game, m), target)
+}
+
+fn same_take(a: &Move, b: &Move) -> bool {
+    a.factory_idx == b.factory_idx
+        && a.color == b.color
+        && a.num_wilds == b.num_wilds
+}
+
+/// The legal moves, grouped by the tiles they take. Each group lists the
+/// rows those tiles can go to.
+fn group_moves(moves: &[Move]) -> Vec<(Move, Vec<usize>)> {
+    let mut groups: Vec<(Move, Vec<usize>)> = vec![];
+    for m in moves {
+        match groups.last_mut() {
+            Some((g, rows)) if same_take(g, m) => rows.push(m.working_row),
+            _ => groups.push((*m, vec![m.working_row])),
+        }
+    }
+    for (_, rows) in &mut groups {
+        rows.sort();
+    }
+    groups
+}
+
+struct Session {
+    game: GameState,
+    agents: Vec<Option<Box<dyn Agent + Send>>>,
+    tutor: Box<dyn Agent + Send>,
+    // Game states from before each of the human's moves, for undo.
+    history: Vec<GameState>,
+    painter: Painter,
+}
+
+impl Session {
+    /// Plays bot moves until it's the human's turn. Returns true if the
+    /// game is over.
+    fn play_bots(&mut self, input: &mut impl BufRead) -> Result<bool, String> {
+        loop {
+            if self.game.is_round_over() {
+                if self.end_round(input)? {
+                    return Ok(true);
+                }
+                continue;
+            }
+            let idx = self.game.curr_player_idx;
+            let Some(agent) = &self.agents[idx] else {
+                return Ok(false);
+            };
+            let m = agent.choose_action(&self.game);
+            println!(
+                "{} takes {}.",
+                self.game.players[idx].display_name,
+                describe(&self.game, &m)
+            );
+            self.game.take_turn(&m)?;
+        }
+    }
+
+    fn end_round(&mut self, input: &mut impl BufRead) -> Result<bool, String> {
+        while let Some((idx, row)) = self.game.pending_placement() {
+            let column = match &self.agents[idx] {
+                Some(agent) => agent.choose_placement(&self.game, idx, row),
+                None => self.ask_placement(idx, row, input)?,
+            };
+            let column = column.ok_or("No column to place in")?;
+            self.game.place_tile(idx, row, column)?;
+        }
+        let round = self.game.round_number();
+        let game_over = self.game.finish_round()?;
+        println!("\nEnd of round {}:", round);
+        for p in &self.game.players {
+            let points = p.ledger().rounds.last().map_or(0, |r| r.total);
+            println!(
+                "  {}: {:+} ({} total)",
+                p.display_name,
+                points,
+                p.score()
+            );
+        }
+        if !game_over {
+            self.game.start_round();
+        }
+        Ok(game_over)
+    }
+
+    fn ask_placement(
+        &self,
+        idx: usize,
+        row: usize,
+        input: &mut impl BufRead,
+    ) -> Result<Option<usize>, String> {
+        let options = self.game.players[idx].placement_options(row);
+        println!("{}", self.painter.game(&self.game).join("\n"));
+        loop {
+            let columns: Vec<String> =
+                options.iter().map(|c| (c + 1).to_string()).collect();
+            print!(
+                "Place row {} in column ({}): ",
+                row + 1,
+                columns.join(", ")
+            );
+            let Some(line) = read_line(input) else {
+                return Ok(self.game.players[idx].best_placement(row));
+            };
+            match parse_column(&options, &line) {
+                Some(column) => return Ok(Some(column)),
+                None => println!("That column isn't available."),
+            }
+        }
+    }
+
+    /// Runs the game until it ends or the human quits.
+    fn run(&mut self, input: &mut impl BufRead) -> Result<(), String> {
+        self.game.start_round();
+        if self.play_bots(input)? {
+            return Ok(());
+        }
+        let mut hint: Option<Move> = None;
+        loop {
+            let groups = group_moves(&self.game.valid_moves());
+            println!("\n{}", self.painter.game(&self.game).join("\n"));
+            for (i, (take, rows)) in groups.iter().enumerate() {
+                let rows: Vec<String> = rows
+                    .iter()
+                    .map(|&row| {
+                        let name = row_name(&self.game, row);
+                        let hinted = hint.is_some_and(|h| {
+                            h.working_row == row && same_take(&h, take)
+                        });
+                        if hinted {
+                            self.painter.bold(&format!("[{}]", name))
+                        } else {
+                            name
+                        }
+                    })
+                    .collect();
+                let text = format!(
+                    "{:3}) {} -> {}",
+                    i + 1,
+                    describe_take(&self.game, take),
+                    rows.join(" ")
+                );
+                println!("{}", text);
+            }
+            if let Some(h) = hint {
+                println!("Hint: take {}.", describe(&self.game, &h));
+            }
+            print!("Your move (? for help): ");
+            let Some(line) = read_line(input) else {
+                return Ok(());
+            };
+            let m = match line.as_str() {
+                "q" => return Ok(()),
+                "?" => {
+                    println!("{}", HELP);
+                    continue;
+                }
+                "h" => {
+                    hint = Some(self.tutor.choose_action(&self.game));
+                    continue;
+                }
+                "u" => {
+                    match self.history.pop() {
+                        Some(prev) => self.game = prev,
+                        None => println!("Nothing to undo."),
+                    }
+                    hint = None;
+                    continue;
+                }
+                _ => match parse_move(&self.game, &groups, &line) {
+                    Some(m) => m,
+                    None => {
+                        println!("Not a legal move: {}", line);
+                        continue;
+                    }
+                },
+            };
+            hint = None;
+            self.history.push(self.game.clone());
+            println!("You take {}.", describe(&self.game, &m));
+            self.game.take_turn(&m)?;
+            if self.play_bots(input)? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Parses "<choice> <row>", where the row is a number or F for the floor.
+fn parse_move(
+    game: &GameState,
+    groups: &[(Move, Vec<usize>)],
+    line: &str,
+) -> Option<Move> {
+    let (choice, row) = line.split_once(' ')?;
+    let (take, rows) =
+        groups.get(choice.trim().parse::<usize>().ok()?.checked_sub(1)?)?;
+    let row = rows
+        .iter()
+        .copied()
+        .find(|&r| row_name(game, r).eq_ignore_ascii_case(row.trim()))?;
+    Some(Move {
+        working_row: row,
+        ..*take
+    })
+}
+
+// Parses a wall column, numbered from 1, if it is one of the `options`.
+fn parse_column(options: &[usize], line: &str) -> Option<usize> {
+    let column = line.trim().parse::<usize>().ok()?.checked_sub(1)?;
+    options.contains(&column).then_some(column)
+}
+
+// Reads a trimmed line, or None at the end of input.
+fn read_line(input: &mut impl BufRead) -> Option<String> {
+    std::io::stdout().flush().ok();
+    let mut line = String::new();
+    match input.read_line(&mut line) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(line.trim().to_string()),
+    }
+}
+
+fn main() -> Result<(), String> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    let opts = parse_args(args.into_iter())
+        .map_err(|e| format!("{}\n\n{}", e, USAGE))?;
+    let mut names = vec![opts.name.clone()];
+    names.extend((1..=opts.bots).map(|i| format!("bot{}", i)));
+    let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+    let seed: [u8; 32] = match opts.seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed).random(),
+        None => rand::rng().random(),
+    };
+    let game = GameState::from_seed(&names, Arc::new(opts.rules), seed)?;
+    let mut agents: Vec<Option<Box<dyn Agent + Send>>> = vec![None];
+    agents.extend((0..opts.bots).map(|_| Some(create_agent(opts.level))));
+    let mut session = Session {
+        game,
+        agents,
+        tutor: create_agent(2),
+        history: vec![],
+        painter: Painter { ansi: opts.ansi },
+    };
+    println!("{}", HELP);
+    session.run(&mut std::io::stdin().lock())?;
+    let game = &session.game;
+    if game.is_finished() {
+        println!("\n{}", session.painter.game(game).join("\n"));
+        println!("Final standings:");
+        for s in game.ranking() {
+            println!(
+                "  {}. {} ({} points)",
+                s.rank, game.players[s.player_idx].display_name, s.score
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+fn seeded_game(rules: RuleSet) -> GameState {
+    let mut game =
+        GameState::from_seed(&["you", "bot"], Arc::new(rules), [7; 32])
+            .unwrap();
+    game.start_round();
+    game
+}
+
+#[test]
+fn parsing_moves() {
+    let game = seeded_game(RuleSet::default());
+    let groups = group_moves(&game.valid_moves());
+    let floor = game.floor_row();
+    let (take, rows) = &groups[2];
+    assert!(rows.contains(&floor));
+    for line in ["3 F", "3 f", "3  f"] {
+        let m = parse_move(&game, &groups, line).unwrap();
+        assert_eq!(m.working_row, floor);
+        assert!(same_take(&m, take));
+    }
+    let m = parse_move(&game, &groups, "3 1").unwrap();
+    assert_eq!(m.working_row, 0);
+    assert!(game.valid_moves().contains(&m));
+    let bad_row = (floor + 1).to_string();
+    for line in ["3", "0 1", "99 1", "x 1", &format!("3 {}", bad_row)] {
+        assert_eq!(parse_move(&game, &groups, line), None, "{line}");
+    }
+}
+
+#[test]
+fn parsing_wild_takes() {
+    let mut rules = RuleSet::default();
+    rules.wilds.count = 20;
+    let game = seeded_game(rules);
+    let groups = group_moves(&game.valid_moves());
+    let choice = groups.iter().position(|(m, _)| m.num_wilds > 0).unwrap();
+    let (take, _) = &groups[choice];
+    // Takes with and without wild tiles are listed separately.
+    assert!(groups.iter().any(|(m, _)| m.num_wilds == 0
+        && m.color == take.color
+        && m.factory_idx == take.factory_idx));
+    let line = format!("{} F", choice + 1);
+    let m = parse_move(&game, &groups, &line).unwrap();
+    assert_eq!(m.num_wilds, take.num_wilds);
+    assert!(describe_take(&game, &m).contains(" wild from "));
+}
+
+#[test]
+fn parsing_columns() {
+    let options = [0, 2, 4];
+    assert_eq!(parse_column(&options, "1"), Some(0));
+    assert_eq!(parse_column(&options, " 5 "), Some(4));
+    for line in ["0", "2", "6", "-1", "x", ""] {
+        assert_eq!(parse_column(&options, line), None, "{line}");
+    }
+}
@@ -0,0 +1,47 @@
+//! Hosts games for local clients over WebSocket, with finished games
+//! available over plain HTTP.
+//!
+//!     blau-server --addr 127.0.0.1:8080 --store games/
+use blau::server::{FileStore, GameStore, Host, MemoryStore, serve};
+use std::net::TcpListener;
+
+const USAGE: &str = "\
+Usage: blau-server [options]
+
+Clients connect to ws://ADDR/ws?player=ID and send JSON requests:
+  {\"type\": \"create\", \"players\": [{\"id\": \"ann\"}, {\"id\": \"bot\", \"level\": 1}]}
+  {\"type\": \"action\", \"game_id\": 1, \"action\": {...}}
+  {\"type\": \"view\", \"game_id\": 1}
+  {\"type\": \"list\"}
+Finished games are at GET /games and GET /games/ID.
+
+Options:
+  --addr ADDR     Address to listen on (default 127.0.0.1:8080).
+  --store DIR     Keep finished games in DIR, instead of in memory.";
+
+fn main() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let mut addr = "127.0.0.1:8080".to_string();
+    let mut store: Box<dyn GameStore> = Box::new(MemoryStore::default());
+    while let Some(arg) = args.next() {
+        if arg == "--help" || arg == "-h" {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}\n\n{}", arg, USAGE))?;
+        match arg.as_str() {
+            "--addr" => addr = value,
+            "--store" => {
+                store =
+                    Box::new(FileStore::new(value).map_err(|e| e.to_string())?)
+            }
+            _ => return Err(format!("Unknown option: {}\n\n{}", arg, USAGE)),
+        }
+    }
+    let host = Host::new(store).map_err(|e| e.to_string())?;
+    let listener = TcpListener::bind(&addr).map_err(|e| e.to_string())?;
+    eprintln!("Listening on {}", addr);
+    serve(listener, host).map_err(|e| e.to_string())
+}
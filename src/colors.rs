@@ -8,13 +8,29 @@ pub enum Color {
     Green,
     Red,
     Purple,
+    // Only used in the six-color variant.
+    Yellow,
     Start,
     Blank,
+    // Stands in for any color, in games with wild tiles.
+    Wild,
 }
 
+/// Every wall color. Standard games use the first five.
+pub const ALL_COLORS: [Color; 6] = [
+    Color::Blue,
+    Color::Orange,
+    Color::Green,
+    Color::Red,
+    Color::Purple,
+    Color::Yellow,
+];
+
 impl Color {
+    // Wild tiles are taken along with the color they stand in for, so moves
+    // never name them directly.
     pub fn is_movable(&self) -> bool {
-        *self != Color::Start && *self != Color::Blank
+        *self != Color::Start && *self != Color::Blank && *self != Color::Wild
     }
 }
 
@@ -23,6 +39,8 @@ fn checks_movable() {
     assert!(Color::Blue.is_movable());
     assert!(!Color::Start.is_movable());
     assert!(!Color::Blank.is_movable());
+    assert!(Color::Yellow.is_movable());
+    assert!(!Color::Wild.is_movable());
 }
 
 impl TryFrom<usize> for Color {
@@ -34,6 +52,7 @@ impl TryFrom<usize> for Color {
             2 => Ok(Color::Green),
             3 => Ok(Color::Red),
             4 => Ok(Color::Purple),
+            5 => Ok(Color::Yellow),
             _ => Err(()),
         }
     }
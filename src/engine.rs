@@ -0,0 +1,712 @@
+//! A line-based protocol for bots that run as separate processes, modeled on
+//! UCI from chess. The host writes commands to the engine's stdin:
+//!
+//! - `blau`: the engine answers with `id name NAME`, any `option name NAME
+//!   type spin default N min N max N` lines, then `blauok`.
+//! - `setoption name NAME value VALUE`
+//! - `isready`: the engine answers `readyok` once it's idle.
+//! - `position json GAME`, with the `GameState` as JSON on one line, but
+//!   without the `tile_bag`, `box_lid` and `rng` that would give away the
+//!   tiles still to come, or
+//!   `position startpos seed N players N [rules NAME] [moves M...]`, which
+//!   deals from the same seed as `GameParams` and replays the moves.
+//! - `go [movetime MS]`: the engine answers `bestmove M`, or `bestmove none`
+//!   if the game is over. Engines may send `info string TEXT` lines first,
+//!   for instance when they play a weaker move than asked for.
+//! - `quit`
+//!
+//! Moves are written as the source (a factory number, or `c` for the
+//! center), the color's letter, the target row (a number, or `f` for the
+//! floor), and `*N` if N wild tiles are taken along, as in `2r3` or `cbf`.
+//! When a round ends with a row waiting for a wall column, the column is
+//! written as `=N`.
+use crate::agent::{
+    Agent, GreedyAgent, create_handicapped_agent, smallest_floor_move,
+};
+use crate::colors::ALL_COLORS;
+use crate::game_state::GameState;
+use crate::player_move::Move;
+use crate::render::tile_letter;
+use crate::rules::{Handicap, RuleSet};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+// How long engines get to start up, and to answer after their time is up.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const GRACE: Duration = Duration::from_secs(1);
+
+/// Writes a move in the compact notation.
+pub fn move_notation(game: &GameState, m: &Move) -> String {
+    let source = if m.is_from_center() {
+        "c".to_string()
+    } else {
+        m.factory_idx.to_string()
+    };
+    let color = tile_letter(m.color).to_ascii_lowercase();
+    let row = if m.working_row == game.floor_row() {
+        "f".to_string()
+    } else {
+        (m.working_row + 1).to_string()
+    };
+    let wilds = if m.num_wilds > 0 {
+        format!("*{}", m.num_wilds)
+    } else {
+        String::new()
+    };
+    format!("{}{}{}{}", source, color, row, wilds)
+}
+
+/// Reads a move in the compact notation, if it's legal in `game`.
+pub fn parse_move(game: &GameState, text: &str) -> Result<Move, String> {
+    let bad = || format!("Bad move: {}", text);
+    let (text, num_wilds) = match text.split_once('*') {
+        Some((m, n)) => (m, n.parse().map_err(|_| bad())?),
+        None => (text, 0),
+    };
+    let digits = text.find(|c: char| !c.is_ascii_digit()).ok_or_else(bad)?;
+    let (factory_idx, rest) = match digits {
+        0 => (0, text.strip_prefix('c').ok_or_else(bad)?),
+        n => (text[..n].parse().map_err(|_| bad())?, &text[n..]),
+    };
+    let mut chars = rest.chars();
+    let letter = chars.next().ok_or_else(bad)?;
+    let color = ALL_COLORS
+        .iter()
+        .copied()
+        .find(|&c| tile_letter(c).eq_ignore_ascii_case(&letter))
+        .ok_or_else(bad)?;
+    let working_row = match chars.as_str() {
+        "f" => game.floor_row(),
+        row => row
+            .parse::<usize>()
+            .ok()
+            .and_then(|r| r.checked_sub(1))
+            .ok_or_else(bad)?,
+    };
+    let m = Move {
+        factory_idx,
+        color,
+        working_row,
+        num_wilds,
+    };
+    if !game.valid_moves().contains(&m) {
+        return Err(format!("Illegal move: {}", text));
+    }
+    Ok(m)
+}
+
+// Finishes a round once its wall columns are chosen, and deals the next.
+fn next_round(game: &mut GameState) -> Result<(), String> {
+    if game.finish_round()? {
+        return Err("The game is over".to_string());
+    }
+    game.start_round();
+    Ok(())
+}
+
+// Writes a game for `position json`, leaving out the tiles still to come.
+fn position_json(game: &GameState) -> Result<String, String> {
+    let mut value = serde_json::to_value(game).map_err(|e| e.to_string())?;
+    if let Some(data) = value.as_object_mut() {
+        for key in ["tile_bag", "box_lid", "rng"] {
+            data.remove(key);
+        }
+    }
+    Ok(value.to_string())
+}
+
+/// Sets up a game from the arguments of a `position` command.
+pub fn parse_position(args: &str) -> Result<GameState, String> {
+    if let Some(json) = args.strip_prefix("json ") {
+        return serde_json::from_str(json).map_err(|e| e.to_string());
+    }
+    let mut words = args.split_whitespace();
+    if words.next() != Some("startpos") {
+        return Err(format!("Unknown position: {}", args));
+    }
+    let (mut seed, mut players, mut rules) = (None, 2, RuleSet::default());
+    let mut moves = vec![];
+    while let Some(word) = words.next() {
+        if word == "moves" {
+            moves.extend(words.by_ref());
+            break;
+        }
+        let value = words
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", word))?;
+        let bad_value = |_| format!("Bad value for {}: {}", word, value);
+        match word {
+            "seed" => seed = Some(value.parse::<u64>().map_err(bad_value)?),
+            "players" => players = value.parse().map_err(bad_value)?,
+            "rules" => rules = RuleSet::preset(value)?,
+            _ => return Err(format!("Unknown position option: {}", word)),
+        }
+    }
+    let seed = seed.ok_or("Missing seed for startpos")?;
+    let names: Vec<String> = (1..=players).map(|i| format!("p{}", i)).collect();
+    let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+    let seed: [u8; 32] = ChaCha8Rng::seed_from_u64(seed).random();
+    let mut game = GameState::from_seed(&names, Arc::new(rules), seed)?;
+    game.start_round();
+    for word in moves {
+        if let Some(column) = word.strip_prefix('=') {
+            let (idx, row) =
+                game.pending_placement().ok_or("No tile to place")?;
+            let column = column
+                .parse::<usize>()
+                .ok()
+                .and_then(|c| c.checked_sub(1))
+                .ok_or_else(|| format!("Bad column: {}", word))?;
+            game.place_tile(idx, row, column)?;
+            continue;
+        }
+        if game.is_round_over() {
+            next_round(&mut game)?;
+        }
+        let m = parse_move(&game, word)?;
+        game.take_turn(&m)?;
+    }
+    if game.is_round_over() && game.pending_placement().is_none() {
+        // Leaves the final position scored, if the game is over.
+        let _ = next_round(&mut game);
+    }
+    Ok(game)
+}
+
+/// Settings for the built-in agents, as engine options.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct EngineOptions {
+    level: usize,
+    // Zero is the usual budget for the level.
+    budget: usize,
+    blunder: u32,
+}
+
+impl EngineOptions {
+    fn agent(&self) -> Box<dyn Agent + Send> {
+        let handicap = Handicap {
+            search_budget: (self.budget > 0).then_some(self.budget),
+            blunder_percent: self.blunder,
+            ..Handicap::default()
+        };
+        create_handicapped_agent(self.level, &handicap, rand::rng().random())
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let bad_value = |_| format!("Bad value for {}: {}", name, value);
+        match name.to_ascii_lowercase().as_str() {
+            "level" => self.level = value.parse().map_err(bad_value)?,
+            "budget" => self.budget = value.parse().map_err(bad_value)?,
+            "blunder" => self.blunder = value.parse().map_err(bad_value)?,
+            _ => return Err(format!("Unknown option: {}", name)),
+        }
+        Ok(())
+    }
+}
+
+// Picks a move or wall column with `agent`. Moves that take longer than
+// `movetime` are replaced by the greedy agent's move, as are moves asked for
+// while an earlier search is still running in `worker`.
+fn best_move(
+    game: &GameState,
+    agent: Box<dyn Agent + Send>,
+    movetime: Option<Duration>,
+    worker: &mut Option<JoinHandle<()>>,
+    output: &mut impl Write,
+) -> std::io::Result<String> {
+    if let Some((idx, row)) = game.pending_placement() {
+        return Ok(match agent.choose_placement(game, idx, row) {
+            Some(column) => format!("={}", column + 1),
+            None => "none".to_string(),
+        });
+    }
+    if game.is_round_over() {
+        return Ok("none".to_string());
+    }
+    let mut m = None;
+    if worker.as_ref().is_none_or(|w| w.is_finished()) {
+        let (sender, receiver) = channel();
+        let state = game.clone();
+        *worker = Some(std::thread::spawn(move || {
+            let _ = sender.send(agent.choose_action(&state));
+        }));
+        m = match movetime {
+            Some(limit) => receiver.recv_timeout(limit).ok(),
+            None => receiver.recv().ok(),
+        };
+    } else {
+        writeln!(
+            output,
+            "info string Still searching an earlier position, so playing \
+             the greedy move"
+        )?;
+    }
+    let m = m.unwrap_or_else(|| GreedyAgent::new().choose_action(game));
+    Ok(move_notation(game, &m))
+}
+
+// Waits for a search that is still running, so that its late answer can't
+// be mistaken for a later one.
+fn finish_search(worker: &mut Option<JoinHandle<()>>) {
+    if let Some(w) = worker.take() {
+        let _ = w.join();
+    }
+}
+
+/// Serves the built-in agents over the engine protocol, until `quit` or the
+/// end of input.
+pub fn run_engine(
+    input: impl BufRead,
+    mut output: impl Write,
+) -> std::io::Result<()> {
+    let mut options = EngineOptions {
+        level: 1,
+        budget: 0,
+        blunder: 0,
+    };
+    let mut game: Option<GameState> = None;
+    let mut worker = None;
+    for line in input.lines() {
+        let line = line?;
+        let (command, args) =
+            line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+        match command {
+            "blau" => {
+                writeln!(output, "id name blau {}", env!("CARGO_PKG_VERSION"))?;
+                writeln!(output, "id author {}", env!("CARGO_PKG_AUTHORS"))?;
+                writeln!(
+                    output,
+                    "option name Level type spin default 1 min 0 max 2"
+                )?;
+                writeln!(
+                    output,
+                    "option name Budget type spin default 0 min 0 max 100000"
+                )?;
+                writeln!(
+                    output,
+                    "option name Blunder type spin default 0 min 0 max 100"
+                )?;
+                writeln!(output, "blauok")?;
+            }
+            "isready" => {
+                finish_search(&mut worker);
+                writeln!(output, "readyok")?;
+            }
+            "setoption" => {
+                let result = args
+                    .strip_prefix("name ")
+                    .and_then(|rest| rest.split_once(" value "))
+                    .ok_or_else(|| format!("Bad setoption: {}", args))
+                    .and_then(|(name, value)| options.set(name, value.trim()));
+                if let Err(e) = result {
+                    writeln!(output, "info string {}", e)?;
+                }
+            }
+            "newgame" => game = None,
+            "position" => match parse_position(args) {
+                Ok(g) => game = Some(g),
+                Err(e) => {
+                    game = None;
+                    writeln!(output, "info string {}", e)?;
+                }
+            },
+            "go" => {
+                let movetime = args
+                    .strip_prefix("movetime ")
+                    .and_then(|ms| ms.trim().parse().ok())
+                    .map(Duration::from_millis);
+                let best = match &game {
+                    Some(g) => best_move(
+                        g,
+                        options.agent(),
+                        movetime,
+                        &mut worker,
+                        &mut output,
+                    )?,
+                    None => "none".to_string(),
+                };
+                writeln!(output, "bestmove {}", best)?;
+            }
+            "quit" => break,
+            "" => {}
+            _ => writeln!(output, "info string Unknown command: {}", command)?,
+        }
+        output.flush()?;
+    }
+    Ok(())
+}
+
+struct Connection {
+    input: Box<dyn Write + Send>,
+    lines: Receiver<String>,
+    child: Option<Child>,
+}
+
+impl Connection {
+    fn send(&mut self, command: &str) -> Result<(), String> {
+        writeln!(self.input, "{}", command)
+            .and_then(|_| self.input.flush())
+            .map_err(|e| format!("Engine stopped: {}", e))
+    }
+
+    // Waits for a line that starts with `prefix`, skipping any others.
+    fn expect(
+        &mut self,
+        prefix: &str,
+        timeout: Option<Duration>,
+    ) -> Result<String, String> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let line = match deadline {
+                Some(d) => {
+                    let left = d.saturating_duration_since(Instant::now());
+                    self.lines.recv_timeout(left)
+                }
+                None => self
+                    .lines
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let line = line.map_err(|e| match e {
+                RecvTimeoutError::Timeout => format!("No {} in time", prefix),
+                RecvTimeoutError::Disconnected => "Engine stopped".to_string(),
+            })?;
+            if let Some(rest) = line.strip_prefix(prefix) {
+                return Ok(rest.trim().to_string());
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.send("quit");
+        if let Some(child) = &mut self.child {
+            let deadline = Instant::now() + GRACE;
+            while Instant::now() < deadline {
+                if let Ok(Some(_)) = child.try_wait() {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Plays by asking another program, over the engine protocol. If the engine
+/// fails to answer in time, or answers with an illegal move, the agent plays
+/// the smallest floor move instead, as with a player out of time.
+pub struct ExternalAgent {
+    // The name the engine gave in its handshake.
+    pub name: String,
+    // Time the engine gets for each move. None waits as long as it takes.
+    pub movetime: Option<Duration>,
+    conn: Mutex<Connection>,
+    failures: AtomicUsize,
+}
+
+impl ExternalAgent {
+    /// Starts `program` and sets the given engine options.
+    pub fn spawn(
+        program: &str,
+        args: &[String],
+        options: &[(String, String)],
+    ) -> Result<Self, String> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Can't start {}: {}", program, e))?;
+        let input = child.stdin.take().ok_or("No engine stdin")?;
+        let output = child.stdout.take().ok_or("No engine stdout")?;
+        Self::connect(output, input, Some(child), options)
+    }
+
+    fn connect(
+        output: impl Read + Send + 'static,
+        input: impl Write + Send + 'static,
+        child: Option<Child>,
+        options: &[(String, String)],
+    ) -> Result<Self, String> {
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(output).lines() {
+                let Ok(line) = line else { break };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut conn = Connection {
+            input: Box::new(input),
+            lines,
+            child,
+        };
+        conn.send("blau")?;
+        let mut name = String::new();
+        loop {
+            let line = conn.expect("", Some(HANDSHAKE_TIMEOUT))?;
+            if let Some(id) = line.strip_prefix("id name ") {
+                name = id.to_string();
+            } else if line == "blauok" {
+                break;
+            }
+        }
+        for (option, value) in options {
+            conn.send(&format!("setoption name {} value {}", option, value))?;
+        }
+        conn.send("isready")?;
+        conn.expect("readyok", Some(HANDSHAKE_TIMEOUT))?;
+        Ok(ExternalAgent {
+            name,
+            movetime: None,
+            conn: Mutex::new(conn),
+            failures: AtomicUsize::new(0),
+        })
+    }
+
+    /// Moves that were replaced because the engine failed to give one.
+    pub fn num_failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    fn ask(&self, game: &GameState) -> Result<String, String> {
+        let json = position_json(game)?;
+        let mut conn = self.conn.lock().map_err(|e| e.to_string())?;
+        // Skips any late answer to an earlier position.
+        conn.send("isready")?;
+        conn.expect("readyok", Some(HANDSHAKE_TIMEOUT))?;
+        conn.send("newgame")?;
+        conn.send(&format!("position json {}", json))?;
+        match self.movetime {
+            Some(t) => conn.send(&format!("go movetime {}", t.as_millis()))?,
+            None => conn.send("go")?,
+        }
+        conn.expect("bestmove ", self.movetime.map(|t| t + GRACE))
+    }
+}
+
+impl Agent for ExternalAgent {
+    fn choose_action(&self, game: &GameState) -> Move {
+        let reply = self.ask(game);
+        match reply.and_then(|m| parse_move(game, &m)) {
+            Ok(m) => m,
+            Err(_) => {
+                self.failures.fetch_add(1, Ordering::Relaxed);
+                smallest_floor_move(game).expect("No moves to choose from")
+            }
+        }
+    }
+
+    fn choose_placement(
+        &self,
+        game: &GameState,
+        player_idx: usize,
+        row: usize,
+    ) -> Option<usize> {
+        let options = game.players[player_idx].placement_options(row);
+        let column = self.ask(game).ok().and_then(|reply| {
+            let column = reply.strip_prefix('=')?.parse::<usize>().ok()?;
+            column.checked_sub(1).filter(|c| options.contains(c))
+        });
+        if column.is_none() {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+            return game.players[player_idx].best_placement(row);
+        }
+        column
+    }
+}
+
+#[cfg(test)]
+fn seeded_game(seed: u64) -> GameState {
+    parse_position(&format!("startpos seed {}", seed)).unwrap()
+}
+
+#[test]
+fn writing_and_reading_moves() {
+    let game = seeded_game(3);
+    for m in game.valid_moves() {
+        let text = move_notation(&game, &m);
+        assert_eq!(parse_move(&game, &text), Ok(m));
+    }
+    let m = Move {
+        factory_idx: 0,
+        color: crate::colors::Color::Blue,
+        working_row: game.floor_row(),
+        num_wilds: 0,
+    };
+    assert_eq!(move_notation(&game, &m), "cbf");
+    assert!(parse_move(&game, "cbf").is_err());
+    assert!(parse_move(&game, "9x1").is_err());
+    assert!(parse_move(&game, "").is_err());
+}
+
+#[test]
+fn replaying_positions() {
+    let mut game = seeded_game(5);
+    let mut moves = vec![];
+    // Play past the end of the first round.
+    while game.round_number() == 1 || moves.len() < 12 {
+        if game.is_round_over() {
+            next_round(&mut game).unwrap();
+        }
+        let m = game.valid_moves()[0];
+        moves.push(move_notation(&game, &m));
+        game.take_turn(&m).unwrap();
+    }
+    let replayed =
+        parse_position(&format!("startpos seed 5 moves {}", moves.join(" ")))
+            .unwrap();
+    assert_eq!(replayed.to_string(), game.to_string());
+    assert_eq!(replayed.valid_moves(), game.valid_moves());
+    let json = position_json(&game).unwrap();
+    assert!(!json.contains("\"rng\"") && !json.contains("\"tile_bag\""));
+    let from_json = parse_position(&format!("json {}", json)).unwrap();
+    assert_eq!(from_json.valid_moves(), game.valid_moves());
+    assert!(parse_position("startpos players 2").is_err());
+    assert!(parse_position("startpos seed 5 moves 1z1").is_err());
+}
+
+#[test]
+fn running_the_engine() {
+    let input = "blau\nsetoption name Level value 0\nsetoption name Depth value 3\nisready\nposition startpos seed 1\ngo movetime 5000\nquit\ngo\n";
+    let mut output = vec![];
+    run_engine(input.as_bytes(), &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(lines[0].starts_with("id name blau "));
+    assert!(lines.contains(&"blauok"));
+    assert!(lines.contains(&"info string Unknown option: Depth"));
+    assert!(lines.contains(&"readyok"));
+    // Quitting stops before the second go.
+    let best: Vec<&str> = lines
+        .iter()
+        .filter_map(|l| l.strip_prefix("bestmove "))
+        .collect();
+    assert_eq!(best.len(), 1);
+    assert!(parse_move(&seeded_game(1), best[0]).is_ok());
+}
+
+// Waits to be released before playing the greedy move, like a search that
+// takes as long as a test needs.
+#[cfg(test)]
+struct BlockedAgent {
+    release: Mutex<Receiver<()>>,
+}
+
+#[cfg(test)]
+impl Agent for BlockedAgent {
+    fn choose_action(&self, game: &GameState) -> Move {
+        let _ = self.release.lock().unwrap().recv();
+        GreedyAgent::new().choose_action(game)
+    }
+}
+
+#[test]
+fn falling_back_to_greedy_moves() {
+    let game = seeded_game(3);
+    let greedy = move_notation(&game, &GreedyAgent::new().choose_action(&game));
+    let (release, receiver) = channel();
+    let mut worker = None;
+    let mut output = vec![];
+    let agent = Box::new(BlockedAgent {
+        release: Mutex::new(receiver),
+    });
+    let limit = Some(Duration::from_millis(10));
+    let best = best_move(&game, agent, limit, &mut worker, &mut output);
+    assert_eq!(best.unwrap(), greedy);
+    assert!(output.is_empty());
+    let searching = worker.as_ref().unwrap().thread().id();
+
+    // No second search is started while the first is still running.
+    let agent = crate::agent::create_agent(2);
+    let best = best_move(&game, agent, None, &mut worker, &mut output);
+    assert_eq!(best.unwrap(), greedy);
+    assert_eq!(worker.as_ref().unwrap().thread().id(), searching);
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with("info string Still searching"));
+
+    release.send(()).unwrap();
+    finish_search(&mut worker);
+    assert!(worker.is_none());
+}
+
+#[test]
+fn playing_against_an_external_engine() {
+    let (engine_in, host_out) = std::io::pipe().unwrap();
+    let (host_in, engine_out) = std::io::pipe().unwrap();
+    std::thread::spawn(move || {
+        run_engine(BufReader::new(engine_in), engine_out).unwrap()
+    });
+    let options = [("Level".to_string(), "0".to_string())];
+    let mut agent =
+        ExternalAgent::connect(host_in, host_out, None, &options).unwrap();
+    agent.movetime = Some(Duration::from_secs(5));
+    assert!(agent.name.starts_with("blau "));
+    let game = seeded_game(2);
+    let expected = crate::agent::create_agent(0).choose_action(&game);
+    assert_eq!(agent.choose_action(&game), expected);
+    assert_eq!(agent.num_failures(), 0);
+}
+
+#[test]
+fn ignoring_late_answers() {
+    let (engine_in, host_out) = std::io::pipe().unwrap();
+    let (host_in, mut engine_out) = std::io::pipe().unwrap();
+    let (release, released) = channel();
+    // Answers the first search only once released, with the first legal
+    // move.
+    std::thread::spawn(move || {
+        let mut game = None;
+        let mut first = true;
+        for line in BufReader::new(engine_in).lines() {
+            let line = line.unwrap();
+            let (command, args) = line.split_once(' ').unwrap_or((&line, ""));
+            let reply = match command {
+                "blau" => "id name slow\nblauok".to_string(),
+                "isready" => "readyok".to_string(),
+                "position" => {
+                    game = parse_position(args).ok();
+                    continue;
+                }
+                "go" => {
+                    if first {
+                        released.recv().unwrap();
+                        first = false;
+                    }
+                    let g: &GameState = game.as_ref().unwrap();
+                    format!(
+                        "bestmove {}",
+                        move_notation(g, &g.valid_moves()[0])
+                    )
+                }
+                _ => continue,
+            };
+            writeln!(engine_out, "{}", reply).unwrap();
+        }
+    });
+    let mut agent =
+        ExternalAgent::connect(host_in, host_out, None, &[]).unwrap();
+    agent.movetime = Some(Duration::from_millis(100));
+    let mut game = seeded_game(2);
+    let first = game.valid_moves()[0];
+    agent.choose_action(&game);
+    assert_eq!(agent.num_failures(), 1);
+    release.send(()).unwrap();
+    game.take_turn(&first).unwrap();
+    // The late answer for the first position isn't taken for this one.
+    agent.movetime = Some(Duration::from_secs(5));
+    assert_eq!(agent.choose_action(&game), game.valid_moves()[0]);
+    assert_eq!(agent.num_failures(), 1);
+}
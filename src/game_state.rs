@@ -1,11 +1,13 @@
 use crate::colors::Color;
 use crate::player_move::Move;
 use crate::player_state::PlayerState;
+use crate::rules::{Handicap, RuleSet};
 use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
 use rand_chacha::ChaCha8Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GameState {
@@ -21,39 +23,115 @@ pub struct GameState {
     start_player_idx: usize,
     pub curr_player_idx: usize,
     round_number: usize,
+    // Positions sent to engines leave out the shuffle, like the bag.
+    #[serde(default = "unseeded_rng")]
     rng: ChaCha8Rng,
+    // Games stored before rule sets existed used the standard rules.
+    #[serde(default)]
+    rules: Arc<RuleSet>,
 }
 
-fn as_vec_len<S>(vec: &Vec<Color>, serializer: S) -> Result<S::Ok, S::Error>
+/// Outcome of scoring the end of a round.
+#[derive(Debug, Clone)]
+pub struct RoundResult {
+    pub game_over: bool,
+    // Tiles each player sent to the box lid.
+    pub discarded: Vec<Vec<Color>>,
+}
+
+/// The changes made by a single move, for clients that already have the
+/// state from before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnDelta {
+    pub player_idx: usize,
+    pub factory_idx: usize,
+    pub color: Color,
+    pub num_taken: usize,
+    // Leftover factory tiles that were pushed to the center.
+    pub to_center: Vec<Color>,
+    pub working_row: usize,
+    // New number of tiles in the working row, if the move targeted one.
+    pub working_count: Option<usize>,
+    // Tiles added to the end of the player's floor line.
+    pub to_floor: Vec<Color>,
+    pub took_start_token: bool,
+    // Wild tiles taken along with the color.
+    #[serde(default)]
+    pub num_wilds: usize,
+}
+
+/// A player's place in the final ranking.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    pub player_idx: usize,
+    // 1 for the winner(s). Tied players share a rank.
+    pub rank: usize,
+    pub score: i32,
+    pub full_rows: i32,
+    // Resigned players rank below everyone who finished the game. In team
+    // play, so do their partners.
+    pub resigned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team: Option<usize>,
+    // Combined score of the player's team, which decides the ranking.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub team_score: Option<i32>,
+    // Included in `score`, so the ranking shows how it was earned.
+    #[serde(default, skip_serializing_if = "Handicap::is_none")]
+    pub handicap: Handicap,
+}
+
+fn as_vec_len<S>(vec: &[Color], serializer: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
     serializer.serialize_i64(vec.len() as i64)
 }
 
-const ALL_COLORS: [Color; 5] = [
-    Color::Blue,
-    Color::Orange,
-    Color::Green,
-    Color::Red,
-    Color::Purple,
-];
+fn unseeded_rng() -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(0)
+}
 
 impl GameState {
-    pub fn new(player_names: &[&str], rng: &mut impl rand::Rng) -> GameState {
+    pub fn new(
+        player_names: &[&str],
+        rng: &mut impl rand::Rng,
+    ) -> Result<GameState, String> {
+        GameState::with_rules(player_names, RuleSet::default(), rng)
+    }
+
+    pub fn with_rules(
+        player_names: &[&str],
+        rules: RuleSet,
+        rng: &mut impl rand::Rng,
+    ) -> Result<GameState, String> {
         // Initialize the internal RNG.
         let seed: [u8; 32] = rng.random();
+        GameState::from_seed(player_names, Arc::new(rules), seed)
+    }
+
+    pub fn from_seed(
+        player_names: &[&str],
+        rules: Arc<RuleSet>,
+        seed: [u8; 32],
+    ) -> Result<GameState, String> {
+        rules.validate(player_names.len())?;
         let mut my_rng = ChaCha8Rng::from_seed(seed);
 
-        let mut tile_bag: Vec<Color> =
-            (0..20).flat_map(|_x| ALL_COLORS.iter()).cloned().collect();
+        let mut tile_bag: Vec<Color> = (0..rules.tiles_per_color)
+            .flat_map(|_x| rules.colors().iter())
+            .cloned()
+            .chain(std::iter::repeat_n(Color::Wild, rules.wilds.count))
+            .collect();
         tile_bag.shuffle(&mut my_rng);
-        let players: Vec<PlayerState> =
-            player_names.iter().map(|&p| PlayerState::new(p)).collect();
+        let players: Vec<PlayerState> = player_names
+            .iter()
+            .map(|&p| PlayerState::for_rules(p, &rules))
+            .collect();
         let mut center = HashMap::new();
         center.insert(Color::Start, 1);
-        let num_factories = player_names.len() * 2 + 1;
-        GameState {
+        let num_factories = rules.factories.num_factories(player_names.len());
+        Ok(GameState {
             tile_bag,
             box_lid: vec![],
             factories: vec![vec![]; num_factories],
@@ -63,7 +141,72 @@ impl GameState {
             curr_player_idx: 0,
             round_number: 0,
             rng: my_rng,
+            rules,
+        })
+    }
+
+    /// Rebuilds the state this game started from, before the first round.
+    /// All randomness flows from the seed, so replaying the same moves on the
+    /// result reproduces the game exactly.
+    pub fn replay_start(&self) -> Result<GameState, String> {
+        let names: Vec<&str> = self
+            .players
+            .iter()
+            .map(|p| p.display_name.as_str())
+            .collect();
+        let mut game = GameState::from_seed(
+            &names,
+            self.rules.clone(),
+            self.rng.get_seed(),
+        )?;
+        for (idx, p) in self.players.iter().enumerate() {
+            game.set_handicap(idx, p.handicap().clone())?;
+        }
+        Ok(game)
+    }
+
+    /// Gives a player a handicap. This must be done before the first round.
+    pub fn set_handicap(
+        &mut self,
+        player_idx: usize,
+        handicap: Handicap,
+    ) -> Result<(), String> {
+        if self.round_number > 0 {
+            return Err(
+                "Handicaps must be set before the game starts.".to_string()
+            );
         }
+        self.players
+            .get_mut(player_idx)
+            .ok_or_else(|| format!("No player #{}", player_idx))?
+            .set_handicap(handicap)
+    }
+
+    pub fn rules(&self) -> &RuleSet {
+        &self.rules
+    }
+
+    /// The rules, for callers that need them while changing the game.
+    pub fn shared_rules(&self) -> Arc<RuleSet> {
+        Arc::clone(&self.rules)
+    }
+
+    pub fn round_number(&self) -> usize {
+        self.round_number
+    }
+
+    pub fn factories(&self) -> &[Vec<Color>] {
+        &self.factories
+    }
+
+    /// Tiles in the center, by color. The start token counts as a tile.
+    pub fn center(&self) -> &HashMap<Color, usize> {
+        &self.center
+    }
+
+    /// The row that moves use for the floor line.
+    pub fn floor_row(&self) -> usize {
+        self.rules.colors().len()
     }
 
     pub fn current_player(&self) -> &PlayerState {
@@ -81,46 +224,110 @@ impl GameState {
     pub fn start_round(&mut self) {
         self.curr_player_idx = self.start_player_idx;
         self.start_player_idx = self.players.len();
+        if self.players[self.curr_player_idx].has_resigned() {
+            self.advance_player();
+        }
         // replace the start token in the center
         self.center.insert(Color::Start, 1);
         // fill factories from the tile bag
-        for factory in &mut self.factories {
-            for _i in 0..4 {
-                if let Some(tile) = self.tile_bag.pop() {
-                    factory.push(tile);
-                } else {
-                    // tile bag is empty, this can happen in later rounds
-                    return;
+        'fill: for factory in &mut self.factories {
+            for _i in 0..self.rules.factories.factory_size {
+                if self.tile_bag.is_empty() {
+                    if self.box_lid.is_empty() {
+                        // All remaining tiles are on players' boards, so
+                        // some factories stay partially filled.
+                        break 'fill;
+                    }
+                    // Refill the bag from the box lid and keep going.
+                    self.tile_bag.append(&mut self.box_lid);
+                    self.tile_bag.shuffle(&mut self.rng);
                 }
+                factory.extend(self.tile_bag.pop());
             }
         }
         self.round_number += 1;
     }
 
-    fn is_round_over(&self) -> bool {
+    pub fn is_round_over(&self) -> bool {
         self.center.is_empty() && self.factories.iter().all(|f| f.is_empty())
     }
 
-    pub fn num_tiles_taken(&self, m: &Move) -> Result<usize, String> {
-        if m.is_from_center() {
-            if let Some(n) = self.center.get(&m.color) {
-                Ok(*n)
-            } else {
-                Err(format!("Color {:?} is not in the center.", m.color))
-            }
+    // Returns the number of tiles of a color in a factory, or in the center
+    // for factory 0.
+    fn num_in_source(&self, factory_idx: usize, color: Color) -> usize {
+        if factory_idx == 0 {
+            self.center.get(&color).copied().unwrap_or(0)
         } else {
-            Ok(self.factories[m.factory_idx - 1]
+            self.factories[factory_idx - 1]
                 .iter()
-                .filter(|&t| *t == m.color)
-                .count())
+                .filter(|&t| *t == color)
+                .count()
         }
     }
 
-    pub fn take_turn(&mut self, m: &Move) -> Result<bool, String> {
-        // println!("player {:?}: {:?}", self.current_player().display_name, m);
-        if let Some(err_msg) = m.check_validity() {
+    /// Returns the number of tiles a move takes, including wild tiles.
+    pub fn num_tiles_taken(&self, m: &Move) -> Result<usize, String> {
+        if m.is_from_center()
+            && m.num_wilds == 0
+            && !self.center.contains_key(&m.color)
+        {
+            return Err(format!("Color {:?} is not in the center.", m.color));
+        }
+        Ok(self.num_in_source(m.factory_idx, m.color) + m.num_wilds)
+    }
+
+    // Checks the parts of a move that depend on the rules.
+    fn check_move(&self, m: &Move) -> Result<(), String> {
+        if let Some(err_msg) = m.check_validity(self.floor_row()) {
             return Err(err_msg);
         }
+        if !self.rules.colors().contains(&m.color) {
+            return Err(format!("{:?} tiles are not in play.", m.color));
+        }
+        if m.num_wilds == 0 {
+            return Ok(());
+        }
+        let wilds = &self.rules.wilds;
+        if m.num_wilds > wilds.per_take {
+            return Err(format!(
+                "At most {} wild tiles can be taken at once.",
+                wilds.per_take
+            ));
+        }
+        if m.num_wilds > self.num_in_source(m.factory_idx, Color::Wild) {
+            return Err(format!(
+                "Not enough wild tiles in factory #{}.",
+                m.factory_idx
+            ));
+        }
+        if !wilds.with_color && self.num_in_source(m.factory_idx, m.color) > 0 {
+            return Err("Wild tiles can't be taken with a color.".to_string());
+        }
+        Ok(())
+    }
+
+    // Returns the factory tiles that a move pushes to the center.
+    fn leftover_tiles(&self, m: &Move) -> Vec<Color> {
+        if m.is_from_center() {
+            return vec![];
+        }
+        let mut wilds_taken = 0;
+        self.factories[m.factory_idx - 1]
+            .iter()
+            .filter(|&t| {
+                if *t == Color::Wild && wilds_taken < m.num_wilds {
+                    wilds_taken += 1;
+                    return false;
+                }
+                *t != m.color
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn take_turn(&mut self, m: &Move) -> Result<bool, String> {
+        // println!("player {:?}: {:?}", self.current_player().display_name, m);
+        self.check_move(m)?;
         let taking_start_token =
             m.is_from_center() && self.is_start_token_available();
         let num_tiles = self.num_tiles_taken(m)?;
@@ -130,22 +337,28 @@ impl GameState {
                 m.color, m.factory_idx
             ));
         }
-        self.current_player_mut().add_tiles(
+        let leftovers = self.leftover_tiles(m);
+        self.current_player_mut().add_tiles_with_wilds(
             m.working_row,
             m.color,
-            num_tiles,
+            num_tiles - m.num_wilds,
+            m.num_wilds,
         )?;
         // Now safe to do mutations to game state.
         if m.is_from_center() {
             self.center.remove(&m.color);
-        } else {
-            let factory = &mut self.factories[m.factory_idx - 1];
-            for t in factory.iter() {
-                if *t != m.color {
-                    *self.center.entry(*t).or_insert(0) += 1;
+            if m.num_wilds > 0 {
+                let wilds = self.center.get_mut(&Color::Wild).unwrap();
+                *wilds -= m.num_wilds;
+                if *wilds == 0 {
+                    self.center.remove(&Color::Wild);
                 }
             }
-            factory.clear();
+        } else {
+            for t in leftovers {
+                *self.center.entry(t).or_insert(0) += 1;
+            }
+            self.factories[m.factory_idx - 1].clear();
         }
         if taking_start_token {
             self.start_player_idx = self.curr_player_idx;
@@ -154,76 +367,649 @@ impl GameState {
         }
         // Check if the round is over.
         if self.is_round_over() {
+            for player in self.players.iter_mut() {
+                player.place_forced_tiles();
+            }
             return Ok(true);
         }
         // Set up for the next player.
-        self.curr_player_idx += 1;
-        self.curr_player_idx %= self.players.len();
+        self.advance_player();
         Ok(false)
     }
 
+    // Moves on to the next player who hasn't resigned.
+    fn advance_player(&mut self) {
+        loop {
+            self.curr_player_idx += 1;
+            self.curr_player_idx %= self.players.len();
+            if !self.current_player().has_resigned() {
+                break;
+            }
+        }
+    }
+
+    /// Returns the player's team in team play. Partners sit opposite each
+    /// other, so players 0 and 2 are team 0, and players 1 and 3 are team 1.
+    pub fn team_of(&self, player_idx: usize) -> Option<usize> {
+        self.rules.team_play.then_some(player_idx % 2)
+    }
+
+    pub fn partner_of(&self, player_idx: usize) -> Option<usize> {
+        self.rules.team_play.then_some((player_idx + 2) % 4)
+    }
+
+    // Returns the player and their partner, if they have one.
+    fn team_members(&self, player_idx: usize) -> Vec<usize> {
+        std::iter::once(player_idx)
+            .chain(self.partner_of(player_idx))
+            .collect()
+    }
+
+    /// Returns the score that counts for a player: their own, or their
+    /// team's combined score in team play.
+    pub fn team_score(&self, player_idx: usize) -> i32 {
+        self.team_members(player_idx)
+            .iter()
+            .map(|&idx| self.players[idx].score())
+            .sum()
+    }
+
+    pub fn num_active_players(&self) -> usize {
+        self.players.iter().filter(|p| !p.has_resigned()).count()
+    }
+
+    /// Removes a player from the turn order, freezing their board and score.
+    /// Returns true if fewer than two players remain, ending the game.
+    pub fn resign(&mut self, player_idx: usize) -> Result<bool, String> {
+        let player = self
+            .players
+            .get_mut(player_idx)
+            .ok_or_else(|| format!("No player #{}", player_idx))?;
+        if player.has_resigned() {
+            return Err(format!("{} already resigned", player.display_name));
+        }
+        // Their tiles go back into circulation.
+        let tiles = player.resign();
+        self.box_lid.extend(tiles);
+        if self.num_active_players() < 2 {
+            return Ok(true);
+        }
+        // A team that has lost both players can't win.
+        if self
+            .partner_of(player_idx)
+            .is_some_and(|idx| self.players[idx].has_resigned())
+        {
+            return Ok(true);
+        }
+        if self.curr_player_idx == player_idx {
+            self.advance_player();
+        }
+        Ok(false)
+    }
+
+    /// Like `take_turn`, but also describes what the move changed.
+    pub fn take_turn_with_delta(
+        &mut self,
+        m: &Move,
+    ) -> Result<(bool, TurnDelta), String> {
+        self.check_move(m)?;
+        let player_idx = self.curr_player_idx;
+        let num_taken = self.num_tiles_taken(m)?;
+        let to_center = self.leftover_tiles(m);
+        let took_start_token =
+            m.is_from_center() && self.is_start_token_available();
+        let floor_len = self.players[player_idx].trashed_tiles().len();
+        let round_over = self.take_turn(m)?;
+        let player = &self.players[player_idx];
+        let delta = TurnDelta {
+            player_idx,
+            factory_idx: m.factory_idx,
+            color: m.color,
+            num_taken,
+            to_center,
+            working_row: m.working_row,
+            working_count: (m.working_row < self.floor_row())
+                .then(|| player.working_count(m.working_row)),
+            to_floor: player.trashed_tiles()[floor_len..].to_vec(),
+            took_start_token,
+            num_wilds: m.num_wilds,
+        };
+        Ok((round_over, delta))
+    }
+
+    /// Returns the next player and row that need a wall column chosen
+    /// before the round can be scored, in free-placement games.
+    pub fn pending_placement(&self) -> Option<(usize, usize)> {
+        if !self.is_round_over() {
+            return None;
+        }
+        self.players.iter().enumerate().find_map(|(idx, p)| {
+            if p.has_resigned() {
+                return None;
+            }
+            p.pending_placement().map(|row| (idx, row))
+        })
+    }
+
+    /// Chooses the wall column for a player's completed row.
+    pub fn place_tile(
+        &mut self,
+        player_idx: usize,
+        row: usize,
+        column: usize,
+    ) -> Result<(), String> {
+        if !self.is_round_over() {
+            return Err("round isn't over".to_string());
+        }
+        self.players
+            .get_mut(player_idx)
+            .ok_or_else(|| format!("No player #{}", player_idx))?
+            .place_tile(row, column)
+    }
+
+    /// Scores the round. In free-placement games, rows still waiting for a
+    /// column are placed where they score the most.
     pub fn finish_round(&mut self) -> Result<bool, String> {
+        Ok(self.finish_round_detailed()?.game_over)
+    }
+
+    /// Like `finish_round`, but also reports where each player's tiles went.
+    pub fn finish_round_detailed(&mut self) -> Result<RoundResult, String> {
         if !self.is_round_over() {
             return Err("round isn't over".to_string());
         }
         // Move and score completed working rows.
+        let mut discarded = Vec::with_capacity(self.players.len());
         for player in &mut self.players {
-            for t in player.score_round()? {
-                self.box_lid.push(t);
+            if player.has_resigned() {
+                discarded.push(vec![]);
+                continue;
             }
+            let tiles = player.score_round(&self.rules)?;
+            self.box_lid.extend_from_slice(&tiles);
+            discarded.push(tiles);
         }
         // Check for the end of the game.
         if self.is_finished() {
-            for player in &mut self.players {
-                player.score_bonuses();
+            for player in self.players.iter_mut() {
+                if !player.has_resigned() {
+                    player.score_bonuses(&self.rules);
+                }
             }
-            return Ok(true);
-        }
-        // Prep the tile bag for the next round, if necessary.
-        if self.tile_bag.len() < 4 * self.factories.len() {
-            self.tile_bag.append(&mut self.box_lid);
-            self.tile_bag.shuffle(&mut self.rng);
+            return Ok(RoundResult {
+                game_over: true,
+                discarded,
+            });
         }
-        Ok(false)
+        Ok(RoundResult {
+            game_over: false,
+            discarded,
+        })
     }
 
     pub fn is_finished(&self) -> bool {
-        self.players.iter().any(|p| p.num_full_rows() > 0)
+        let full_rows = self.rules.full_rows_to_end;
+        (full_rows > 0
+            && self.players.iter().any(|p| p.num_full_rows() >= full_rows))
+            || self
+                .rules
+                .max_rounds
+                .is_some_and(|n| self.round_number >= n)
+            || self.is_exhausted()
+    }
+
+    // True if there are no tiles left to take, or to deal for a new round.
+    // The rules don't cover this case, so we end the game.
+    fn is_exhausted(&self) -> bool {
+        self.tile_bag.is_empty()
+            && self.box_lid.is_empty()
+            && self.factories.iter().all(|f| f.is_empty())
+            && self.center.keys().all(|c| *c == Color::Start)
+    }
+
+    /// Ranks players by score, breaking ties by the number of completed
+    /// horizontal rows. Players who are still tied share the same rank.
+    /// Players who resigned are ranked last. In team play, partners are
+    /// ranked together by their combined score and rows.
+    pub fn ranking(&self) -> Vec<Standing> {
+        let team_key = |player_idx: usize| {
+            let members = self.team_members(player_idx);
+            let players = members.iter().map(|&idx| &self.players[idx]);
+            (
+                players.clone().any(|p| p.has_resigned()),
+                -players.clone().map(|p| p.score()).sum::<i32>(),
+                -players.map(|p| p.num_full_rows()).sum::<i32>(),
+            )
+        };
+        let mut standings: Vec<Standing> = self
+            .players
+            .iter()
+            .enumerate()
+            .map(|(player_idx, p)| Standing {
+                player_idx,
+                rank: 0,
+                score: p.score(),
+                full_rows: p.num_full_rows(),
+                resigned: p.has_resigned(),
+                team: self.team_of(player_idx),
+                team_score: self
+                    .rules
+                    .team_play
+                    .then(|| self.team_score(player_idx)),
+                handicap: p.handicap().clone(),
+            })
+            .collect();
+        let key = |s: &Standing| team_key(s.player_idx);
+        standings.sort_by_key(|s| (key(s), s.team, s.player_idx));
+        for i in 0..standings.len() {
+            standings[i].rank =
+                if i > 0 && key(&standings[i]) == key(&standings[i - 1]) {
+                    standings[i - 1].rank
+                } else {
+                    i + 1
+                };
+        }
+        standings
+    }
+
+    /// Returns the indices of all players who share first place.
+    pub fn winners(&self) -> Vec<usize> {
+        self.ranking()
+            .iter()
+            .filter(|s| s.rank == 1)
+            .map(|s| s.player_idx)
+            .collect()
     }
 
     pub fn valid_moves(&self) -> Vec<Move> {
         let player = self.current_player();
+        let colors = self.rules.colors();
         let color_moves: Vec<Vec<usize>> =
-            ALL_COLORS.iter().map(|c| player.valid_moves(*c)).collect();
+            colors.iter().map(|c| player.valid_moves(*c)).collect();
+        let wilds = &self.rules.wilds;
         let mut result = vec![];
-        // Consider all colors from each factory.
-        for (fidx, factory) in self.factories.iter().enumerate() {
-            for (cidx, c) in ALL_COLORS.iter().enumerate() {
-                if factory.iter().any(|t| t == c) {
+        // Consider all colors from each factory, then from the center.
+        for factory_idx in (1..=self.factories.len()).chain([0]) {
+            let num_wilds = self
+                .num_in_source(factory_idx, Color::Wild)
+                .min(wilds.per_take);
+            for (cidx, c) in colors.iter().enumerate() {
+                let has_color = self.num_in_source(factory_idx, *c) > 0;
+                let max_wilds = if has_color && !wilds.with_color {
+                    0
+                } else {
+                    num_wilds
+                };
+                // Without tiles of the color, only wild tiles can stand in.
+                let min_wilds = if has_color { 0 } else { 1 };
+                for num_wilds in min_wilds..=max_wilds {
                     for row in color_moves[cidx].iter() {
                         result.push(Move {
-                            factory_idx: fidx + 1,
+                            factory_idx,
                             color: *c,
                             working_row: *row,
+                            num_wilds,
                         });
                     }
                 }
             }
         }
-        // Consider all colors from the center.
-        for c in self.center.keys() {
-            if *c == Color::Start {
-                continue;
-            }
-            for row in color_moves[*c as usize].iter() {
-                result.push(Move {
-                    factory_idx: 0,
-                    color: *c,
-                    working_row: *row,
-                });
-            }
-        }
         result
     }
 }
 
+#[test]
+fn ranking_breaks_ties_by_full_rows() {
+    let mut rng = rand::rng();
+    let mut game = GameState::new(&["a", "b", "c", "d"], &mut rng).unwrap();
+    let scores = [(10, 0), (12, 0), (10, 1), (10, 0)];
+    for (p, (score, rows)) in game.players.iter_mut().zip(scores) {
+        p.set_score_for_test(score, rows);
+    }
+    let ranking: Vec<(usize, usize)> = game
+        .ranking()
+        .iter()
+        .map(|s| (s.player_idx, s.rank))
+        .collect();
+    assert_eq!(ranking, vec![(1, 1), (2, 2), (0, 3), (3, 3)]);
+    assert_eq!(game.winners(), vec![1]);
+
+    game.players[2].set_score_for_test(12, 0);
+    assert_eq!(game.winners(), vec![1, 2]);
+}
+
+#[test]
+fn describing_a_turn() {
+    let mut rng = rand::rng();
+    let mut game = GameState::new(&["a", "b"], &mut rng).unwrap();
+    game.start_round();
+    let factory = game.factories[0].clone();
+    let color = factory[0];
+    let m = Move {
+        factory_idx: 1,
+        color,
+        working_row: 0,
+        num_wilds: 0,
+    };
+    let (round_over, delta) = game.take_turn_with_delta(&m).unwrap();
+    assert!(!round_over);
+    let num_taken = factory.iter().filter(|&t| *t == color).count();
+    assert_eq!(delta.num_taken, num_taken);
+    assert_eq!(delta.to_center.len(), 4 - num_taken);
+    assert_eq!(delta.working_count, Some(1));
+    assert_eq!(delta.to_floor, vec![color; num_taken - 1]);
+    assert!(!delta.took_start_token);
+
+    // Taking from the center first also takes the start token.
+    let m = Move {
+        factory_idx: 0,
+        color: delta.to_center[0],
+        working_row: 5,
+        num_wilds: 0,
+    };
+    let (_, delta) = game.take_turn_with_delta(&m).unwrap();
+    assert!(delta.took_start_token);
+    assert_eq!(delta.working_count, None);
+    assert_eq!(delta.to_floor.last(), Some(&Color::Start));
+}
+
+#[test]
+fn resigning_players_are_skipped() {
+    let mut rng = rand::rng();
+    let mut game = GameState::new(&["a", "b", "c"], &mut rng).unwrap();
+    game.start_round();
+    let first = game.curr_player_idx;
+    let second = (first + 1) % 3;
+    let third = (first + 2) % 3;
+    assert_eq!(game.resign(first), Ok(false));
+    assert_eq!(game.curr_player_idx, second);
+    assert!(game.resign(first).is_err());
+
+    let m = game.valid_moves()[0];
+    game.take_turn(&m).unwrap();
+    assert_eq!(game.curr_player_idx, third);
+    let m = game.valid_moves()[0];
+    game.take_turn(&m).unwrap();
+    assert_eq!(game.curr_player_idx, second);
+
+    game.players[first].set_score_for_test(50, 1);
+    let ranking = game.ranking();
+    assert_eq!(ranking[2].player_idx, first);
+    assert!(ranking[2].resigned);
+    assert!(!game.winners().contains(&first));
+
+    // The game ends when only one player is left. Tiles on the board of a
+    // player who resigns go to the box lid.
+    let p = &game.players[second];
+    let on_board = (0..p.num_rows())
+        .map(|row| p.working_count(row))
+        .sum::<usize>()
+        + p.trashed_tiles()
+            .iter()
+            .filter(|&&c| c != Color::Start)
+            .count();
+    assert!(on_board > 0);
+    let lid = game.box_lid.len();
+    assert_eq!(game.resign(second), Ok(true));
+    assert_eq!(game.box_lid.len(), lid + on_board);
+    let p = &game.players[second];
+    assert!((0..p.num_rows()).all(|row| p.working_count(row) == 0));
+    assert!(p.trashed_tiles().is_empty());
+    assert_eq!(game.winners(), vec![third]);
+}
+
+#[test]
+fn refilling_from_the_box_lid() {
+    let mut rng = rand::rng();
+    let mut game = GameState::new(&["a", "b"], &mut rng).unwrap();
+    game.tile_bag = vec![Color::Red; 6];
+    game.box_lid = vec![Color::Blue; 18];
+    game.start_round();
+    assert_eq!(game.round_number, 1);
+    assert!(game.factories.iter().all(|f| f.len() == 4));
+    assert_eq!(game.tile_bag.len(), 4);
+    assert!(game.box_lid.is_empty());
+    let num_red: usize = game
+        .factories
+        .iter()
+        .map(|f| f.iter().filter(|&t| *t == Color::Red).count())
+        .sum();
+    assert_eq!(num_red, 6);
+}
+
+#[test]
+fn partially_filled_factories() {
+    let mut rng = rand::rng();
+    let mut game = GameState::new(&["a", "b"], &mut rng).unwrap();
+    game.tile_bag = vec![Color::Red; 5];
+    game.start_round();
+    assert_eq!(game.round_number, 1);
+    let sizes: Vec<usize> = game.factories.iter().map(|f| f.len()).collect();
+    assert_eq!(sizes, vec![4, 1, 0, 0, 0]);
+    assert!(!game.valid_moves().is_empty());
+}
+
+#[test]
+fn running_out_of_tiles_ends_the_game() {
+    let mut rng = ChaCha8Rng::seed_from_u64(36);
+    let mut game = GameState::new(&["a", "b"], &mut rng).unwrap();
+    game.tile_bag = vec![Color::Red, Color::Blue];
+    game.start_round();
+    // Both tiles go to the floor, and from there to the box lid.
+    loop {
+        let m = *game
+            .valid_moves()
+            .iter()
+            .find(|m| m.working_row == game.floor_row())
+            .unwrap();
+        if game.take_turn(&m).unwrap() {
+            break;
+        }
+    }
+    assert!(game.tile_bag.is_empty());
+    assert_eq!(game.finish_round(), Ok(false));
+    assert_eq!(game.box_lid.len(), 2);
+    // Tiles in the box lid are dealt again next round.
+    game.start_round();
+    assert!(game.box_lid.is_empty());
+    let dealt: Vec<Color> = game.factories.iter().flatten().copied().collect();
+    assert_eq!(dealt.len(), 2);
+    assert!(dealt.contains(&Color::Red) && dealt.contains(&Color::Blue));
+
+    // With no tiles anywhere, the game ends instead of deadlocking.
+    game.factories.iter_mut().for_each(|f| f.clear());
+    game.center.clear();
+    game.tile_bag.clear();
+    game.box_lid.clear();
+    assert_eq!(game.finish_round(), Ok(true));
+    assert!(game.is_finished());
+}
+
+#[test]
+fn validating_player_counts() {
+    let mut rng = rand::rng();
+    for names in [&[][..], &["a"], &["a", "b", "c", "d", "e"]] {
+        assert!(GameState::new(names, &mut rng).is_err());
+    }
+    let game = GameState::new(&["a", "b", "c", "d"], &mut rng).unwrap();
+    assert_eq!(game.factories.len(), 9);
+}
+
+#[test]
+fn custom_rules() {
+    let mut rng = rand::rng();
+    let mut rules = RuleSet::quick();
+    rules.factories.num_factories = Some(3);
+    rules.factories.factory_size = 6;
+    let mut game = GameState::with_rules(&["a", "b"], rules, &mut rng).unwrap();
+    game.start_round();
+    let sizes: Vec<usize> = game.factories.iter().map(|f| f.len()).collect();
+    assert_eq!(sizes, vec![6, 6, 6]);
+    assert_eq!(game.replay_start().unwrap().rules(), game.rules());
+
+    // Quick games end after three rounds.
+    for round in 1..=3 {
+        while !game.take_turn(&game.valid_moves()[0]).unwrap() {}
+        assert_eq!(game.finish_round().unwrap(), round == 3);
+        game.start_round();
+    }
+
+    // The rule set is stored with the game.
+    let json = serde_json::to_string(&game).unwrap();
+    assert!(json.contains(r#""rules":{"name":"quick","#));
+    let mut rules = RuleSet::default();
+    rules.factories.num_factories = Some(0);
+    assert!(GameState::with_rules(&["a", "b"], rules, &mut rng).is_err());
+}
+
+#[test]
+fn playing_on_a_free_wall() {
+    use crate::agent::{Agent, GreedyAgent};
+    let mut rng = rand::rng();
+    let rules = RuleSet::gray();
+    let mut game = GameState::with_rules(&["a", "b"], rules, &mut rng).unwrap();
+    assert!(game.place_tile(0, 0, 0).is_err());
+    let agent = GreedyAgent::new();
+    loop {
+        game.start_round();
+        while !game.take_turn(&agent.choose_action(&game)).unwrap() {}
+        // Choose the leftmost column for each row that needs a choice.
+        while let Some((idx, row)) = game.pending_placement() {
+            let column = game.players[idx].placement_options(row)[0];
+            game.place_tile(idx, row, column).unwrap();
+        }
+        if game.finish_round().unwrap() {
+            break;
+        }
+    }
+    for p in &game.players {
+        for row in 0..5 {
+            let colors: Vec<Color> =
+                (0..5).filter_map(|col| p.wall_color(row, col)).collect();
+            let mut unique = colors.clone();
+            unique.sort_by_key(|c| *c as usize);
+            unique.dedup();
+            assert_eq!(colors.len(), unique.len());
+        }
+    }
+}
+
+#[test]
+fn playing_with_wild_tiles_and_six_colors() {
+    use crate::agent::{Agent, GreedyAgent};
+    let mut rng = rand::rng();
+    let mut rules = RuleSet {
+        six_colors: true,
+        ..Default::default()
+    };
+    rules.wilds.count = 10;
+    rules.wilds.per_take = 2;
+    let mut game = GameState::with_rules(&["a", "b"], rules, &mut rng).unwrap();
+    assert_eq!(game.tile_bag.len(), 130);
+    assert_eq!(game.floor_row(), 6);
+    game.start_round();
+    game.factories[0] = vec![Color::Wild, Color::Wild, Color::Wild, Color::Red];
+    let m = Move {
+        factory_idx: 1,
+        color: Color::Red,
+        working_row: 2,
+        num_wilds: 3,
+    };
+    assert!(game.take_turn(&m).is_err());
+    let m = Move { num_wilds: 2, ..m };
+    assert!(game.valid_moves().contains(&m));
+    let (_, delta) = game.take_turn_with_delta(&m).unwrap();
+    assert_eq!(delta.num_taken, 3);
+    assert_eq!(delta.to_center, vec![Color::Wild]);
+    assert_eq!(delta.working_count, Some(3));
+    assert_eq!(game.center.get(&Color::Wild), Some(&1));
+
+    // The leftover wild can be taken on its own, standing in for yellow.
+    let m = Move {
+        factory_idx: 0,
+        color: Color::Yellow,
+        working_row: 0,
+        num_wilds: 1,
+    };
+    assert!(game.valid_moves().contains(&m));
+    game.take_turn(&m).unwrap();
+    assert!(!game.center.contains_key(&Color::Wild));
+
+    let agent = GreedyAgent::new();
+    while !game.is_finished() {
+        while !game.take_turn(&agent.choose_action(&game)).unwrap() {}
+        if !game.finish_round().unwrap() {
+            game.start_round();
+        }
+    }
+    let num_tiles = game.tile_bag.len()
+        + game.box_lid.len()
+        + game
+            .players
+            .iter()
+            .map(|p| {
+                (0..6)
+                    .map(|row| {
+                        p.working_count(row)
+                            + (0..6)
+                                .filter(|&c| p.wall_color(row, c).is_some())
+                                .count()
+                    })
+                    .sum::<usize>()
+            })
+            .sum::<usize>();
+    assert_eq!(num_tiles, 130);
+}
+
+#[test]
+fn ranking_teams() {
+    let mut rng = rand::rng();
+    let rules = RuleSet {
+        team_play: true,
+        ..Default::default()
+    };
+    let names = ["a", "b", "c", "d"];
+    let mut game = GameState::with_rules(&names, rules, &mut rng).unwrap();
+    assert_eq!(game.partner_of(1), Some(3));
+    assert_eq!(game.team_of(2), Some(0));
+    let scores = [(20, 0), (15, 1), (1, 0), (10, 0)];
+    for (p, (score, rows)) in game.players.iter_mut().zip(scores) {
+        p.set_score_for_test(score, rows);
+    }
+    // Team 1 wins with 25 points against 21.
+    assert_eq!(game.team_score(0), 21);
+    assert_eq!(game.winners(), vec![1, 3]);
+    let ranking = game.ranking();
+    assert_eq!(ranking[2].player_idx, 0);
+    assert_eq!(ranking[2].rank, 3);
+    assert_eq!(ranking[3].rank, 3);
+    assert_eq!(ranking[3].team_score, Some(21));
+
+    // Losing both partners ends the game.
+    game.start_round();
+    assert_eq!(game.resign(1), Ok(false));
+    assert_eq!(game.resign(3), Ok(true));
+    assert_eq!(game.winners(), vec![0, 2]);
+}
+
+#[test]
+fn handicaps_are_kept() {
+    let mut rng = rand::rng();
+    let mut game = GameState::new(&["a", "b"], &mut rng).unwrap();
+    let handicap = Handicap {
+        start_score: 10,
+        wall_tiles: vec![(2, 2)],
+        ..Default::default()
+    };
+    game.set_handicap(1, handicap.clone()).unwrap();
+    assert_eq!(game.winners(), vec![1]);
+    assert_eq!(game.ranking()[0].handicap, handicap);
+    assert!(game.ranking()[1].handicap.is_none());
+    assert_eq!(
+        game.replay_start().unwrap().players[1].handicap(),
+        &handicap
+    );
+
+    game.start_round();
+    assert!(game.set_handicap(0, handicap).is_err());
+}
@@ -1,8 +1,19 @@
 pub mod agent;
+pub mod analysis;
 pub mod api;
+pub mod arena;
 pub mod colors;
+pub mod engine;
 pub mod game_state;
 pub mod player_move;
 pub mod player_state;
 #[cfg(feature = "cpython")]
 mod python_module;
+pub mod render;
+pub mod rules;
+pub mod scoring;
+pub mod series;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod stats;
+pub mod svg;
@@ -1,21 +1,27 @@
 use crate::colors::Color;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Move {
     pub factory_idx: usize,
     pub color: Color,
     pub working_row: usize,
+    // Wild tiles taken to stand in for `color`.
+    #[serde(default)]
+    pub num_wilds: usize,
 }
 
 impl Move {
     pub fn is_from_center(&self) -> bool {
         self.factory_idx == 0
     }
-    pub fn check_validity(&self) -> Option<String> {
+    /// Checks the move against a wall with `num_rows` rows. Row `num_rows`
+    /// is the floor line.
+    pub fn check_validity(&self, num_rows: usize) -> Option<String> {
         if !self.color.is_movable() {
             return Some(format!("{:?} tiles are not movable.", self.color));
         }
-        if self.working_row > 5 {
+        if self.working_row > num_rows {
             return Some(format!("Cannot move to row {}.", self.working_row));
         }
         None
@@ -28,9 +34,10 @@ fn catches_invalid_color() {
         Move {
             factory_idx: 0,
             color: Color::Start,
-            working_row: 1
+            working_row: 1,
+            num_wilds: 0,
         }
-        .check_validity(),
+        .check_validity(5),
         Some("Start tiles are not movable.".to_string())
     );
 }
@@ -41,9 +48,10 @@ fn catches_invalid_destination() {
         Move {
             factory_idx: 0,
             color: Color::Blue,
-            working_row: 6
+            working_row: 6,
+            num_wilds: 0,
         }
-        .check_validity(),
+        .check_validity(5),
         Some("Cannot move to row 6.".to_string())
     );
 }
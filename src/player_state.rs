@@ -1,40 +1,59 @@
 use crate::colors::Color;
+use crate::rules::{Handicap, RuleSet};
+use crate::scoring::{Bonus, FloorPenalty, RoundScore, ScoreLedger, TileScore};
 use serde::{Deserialize, Serialize};
 
-type PlayGrid = [[bool; 5]; 5];
+type PlayGrid = Vec<Vec<bool>>;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerState {
     pub display_name: String,
     played_tiles: PlayGrid,
-    working_count: [usize; 5],
-    working_color: [Color; 5],
+    working_count: Vec<usize>,
+    working_color: Vec<Color>,
+    // Number of wild tiles in each working row.
+    #[serde(default)]
+    working_wilds: Vec<usize>,
     trashed_tiles: Vec<Color>,
-    scores: Vec<i32>,
+    #[serde(alias = "scores")]
+    ledger: ScoreLedger,
+    #[serde(default)]
+    resigned: bool,
+    // Colors on a free-placement wall, with blanks for empty spaces. None
+    // on the standard wall, where each space has a fixed color.
+    #[serde(default)]
+    free_wall: Option<Vec<Vec<Color>>>,
+    // Wall columns chosen for completed rows, before they are scored.
+    #[serde(default)]
+    placements: Vec<Option<usize>>,
+    #[serde(default, skip_serializing_if = "Handicap::is_none")]
+    handicap: Handicap,
 }
 
-static PENALTIES: [i32; 7] = [-1, -1, -2, -2, -2, -3, -3];
-static ROW_BONUS: i32 = 2;
-static COL_BONUS: i32 = 7;
-static KIND_BONUS: i32 = 10;
-
-pub fn played_column(row: usize, color: Color) -> usize {
-    (color as usize + row) % 5
+/// Returns the column of a color on a standard wall with `size` rows.
+pub fn played_column(row: usize, color: Color, size: usize) -> usize {
+    (color as usize + row) % size
 }
 
-fn played_color(row: usize, column: usize) -> usize {
-    (column + 5 - row) % 5
+fn played_color(row: usize, column: usize, size: usize) -> usize {
+    (column + size - row) % size
 }
 
-fn score_tile(grid: &PlayGrid, row: usize, col: usize) -> i32 {
-    let line = grid[row];
+// Returns the lengths of the horizontal and vertical runs through a tile.
+fn tile_runs(grid: &[Vec<bool>], row: usize, col: usize) -> (i32, i32) {
+    let line = &grid[row];
     let horiz = 1
         + line[(col + 1)..].iter().take_while(|&x| *x).count()
         + line[..col].iter().rev().take_while(|&x| *x).count();
     let vert = 1
         + grid[(row + 1)..].iter().take_while(|x| x[col]).count()
         + grid[..row].iter().rev().take_while(|x| x[col]).count();
-    let res = (horiz + vert) as i32;
+    (horiz as i32, vert as i32)
+}
+
+fn score_tile(grid: &[Vec<bool>], row: usize, col: usize) -> i32 {
+    let (horiz, vert) = tile_runs(grid, row, col);
+    let res = horiz + vert;
     if horiz == 1 || vert == 1 {
         res - 1
     } else {
@@ -44,7 +63,7 @@ fn score_tile(grid: &PlayGrid, row: usize, col: usize) -> i32 {
 
 #[test]
 fn scoring_a_tile() {
-    let mut grid = [[false; 5]; 5];
+    let mut grid = vec![vec![false; 5]; 5];
     grid[2][2] = true;
     assert_eq!(score_tile(&grid, 2, 2), 1);
     grid[2][4] = true;
@@ -60,18 +79,129 @@ fn scoring_a_tile() {
 
 impl PlayerState {
     pub fn new(name: &str) -> PlayerState {
+        PlayerState::with_wall_size(name, 5)
+    }
+
+    fn with_wall_size(name: &str, size: usize) -> PlayerState {
         PlayerState {
             display_name: name.to_string(),
-            played_tiles: [[false; 5]; 5],
-            working_count: [0; 5],
-            working_color: [Color::Blank; 5],
+            played_tiles: vec![vec![false; size]; size],
+            working_count: vec![0; size],
+            working_color: vec![Color::Blank; size],
+            working_wilds: vec![0; size],
             trashed_tiles: vec![],
-            scores: vec![],
+            ledger: ScoreLedger::default(),
+            resigned: false,
+            free_wall: None,
+            placements: vec![None; size],
+            handicap: Handicap::default(),
         }
     }
 
+    /// Creates a player with the wall used by a rule set. Free-placement
+    /// walls have no fixed colors, so completed rows can be placed in any
+    /// column where the color isn't already used.
+    pub fn for_rules(name: &str, rules: &RuleSet) -> PlayerState {
+        let size = rules.colors().len();
+        let mut player = PlayerState::with_wall_size(name, size);
+        if rules.free_placement {
+            player.free_wall = Some(vec![vec![Color::Blank; size]; size]);
+        }
+        player
+    }
+
+    /// Number of rows (and columns) on the wall. The floor line is the row
+    /// after the last one.
+    pub fn num_rows(&self) -> usize {
+        self.working_count.len()
+    }
+
+    /// The player's total score, including any handicap.
     pub fn score(&self) -> i32 {
-        self.scores.iter().sum()
+        self.ledger.total() + self.handicap.start_score
+    }
+
+    pub fn handicap(&self) -> &Handicap {
+        &self.handicap
+    }
+
+    /// Gives the player a handicap, tiling any pre-placed wall spaces with
+    /// the colors of the standard wall.
+    pub(crate) fn set_handicap(
+        &mut self,
+        handicap: Handicap,
+    ) -> Result<(), String> {
+        let size = self.num_rows();
+        for &(row, column) in &handicap.wall_tiles {
+            if row >= size || column >= size {
+                return Err(format!(
+                    "wall space ({}, {}) is off the wall",
+                    row, column
+                ));
+            }
+        }
+        for &(row, column) in &handicap.wall_tiles {
+            self.played_tiles[row][column] = true;
+            if let Some(wall) = &mut self.free_wall {
+                wall[row][column] =
+                    Color::try_from(played_color(row, column, size)).unwrap();
+            }
+        }
+        self.handicap = handicap;
+        Ok(())
+    }
+
+    pub fn ledger(&self) -> &ScoreLedger {
+        &self.ledger
+    }
+
+    pub fn has_resigned(&self) -> bool {
+        self.resigned
+    }
+
+    pub fn working_count(&self, row: usize) -> usize {
+        self.working_count[row]
+    }
+
+    /// The color a working row is collecting, or None if it is empty.
+    pub fn working_color(&self, row: usize) -> Option<Color> {
+        (self.working_count[row] > 0).then_some(self.working_color[row])
+    }
+
+    // Players stored before wild tiles existed have no wild counts.
+    pub fn working_wilds(&self, row: usize) -> usize {
+        self.working_wilds.get(row).copied().unwrap_or(0)
+    }
+
+    pub fn trashed_tiles(&self) -> &[Color] {
+        &self.trashed_tiles
+    }
+
+    /// Leaves the game, handing back the tiles on the working rows and floor
+    /// so that they can go in the box lid.
+    pub(crate) fn resign(&mut self) -> Vec<Color> {
+        self.resigned = true;
+        let mut tiles = vec![];
+        for row in 0..self.num_rows() {
+            let colored = self.working_count[row] - self.working_wilds(row);
+            tiles.extend(std::iter::repeat_n(self.working_color[row], colored));
+            tiles.extend(std::iter::repeat_n(
+                Color::Wild,
+                self.working_wilds(row),
+            ));
+            self.clear_working_row(row);
+        }
+        tiles.extend(
+            self.trashed_tiles.drain(..).filter(|c| *c != Color::Start),
+        );
+        tiles
+    }
+
+    /// True if an unfinished working row is collecting this color.
+    pub fn is_collecting(&self, c: Color) -> bool {
+        (0..self.num_rows()).any(|row| {
+            self.working_color[row] == c && !self.is_row_complete(row)
+        })
     }
 
     pub fn is_new_working_row(&self, row: usize) -> bool {
@@ -85,7 +215,143 @@ impl PlayerState {
     }
 
     fn is_played(&self, row: usize, c: Color) -> bool {
-        self.played_tiles[row][played_column(row, c)]
+        match &self.free_wall {
+            Some(wall) => wall[row].contains(&c),
+            None => {
+                self.played_tiles[row][played_column(row, c, self.num_rows())]
+            }
+        }
+    }
+
+    /// Returns the color on the wall at a space, if it has been tiled.
+    pub fn wall_color(&self, row: usize, column: usize) -> Option<Color> {
+        if !self.played_tiles[row][column] {
+            return None;
+        }
+        match &self.free_wall {
+            Some(wall) => Some(wall[row][column]),
+            None => {
+                Color::try_from(played_color(row, column, self.num_rows())).ok()
+            }
+        }
+    }
+
+    /// Returns the color that belongs in a wall space, on the standard wall.
+    /// Spaces on a free-placement wall have no fixed color.
+    pub fn wall_pattern(&self, row: usize, column: usize) -> Option<Color> {
+        if self.free_wall.is_some() {
+            return None;
+        }
+        Color::try_from(played_color(row, column, self.num_rows())).ok()
+    }
+
+    // Returns the empty columns in a row where a color could be placed.
+    fn wall_columns(&self, row: usize, c: Color) -> Vec<usize> {
+        let size = self.num_rows();
+        if self.free_wall.is_none() {
+            let column = played_column(row, c, size);
+            return if self.played_tiles[row][column] {
+                vec![]
+            } else {
+                vec![column]
+            };
+        }
+        if self.is_played(row, c) {
+            return vec![];
+        }
+        (0..size)
+            .filter(|&col| {
+                !self.played_tiles[row][col]
+                    && (0..size).all(|i| self.wall_color(i, col) != Some(c))
+            })
+            .collect()
+    }
+
+    fn is_row_complete(&self, row: usize) -> bool {
+        self.working_count[row] > row
+    }
+
+    // Games stored before placements existed have none to read.
+    fn placement(&self, row: usize) -> Option<usize> {
+        self.placements.get(row).copied().flatten()
+    }
+
+    fn set_placement(&mut self, row: usize, column: Option<usize>) {
+        self.placements.resize(self.num_rows(), None);
+        self.placements[row] = column;
+    }
+
+    /// Returns the columns where a completed row can be placed this round.
+    /// Columns already chosen for the same color in other rows are excluded.
+    pub fn placement_options(&self, row: usize) -> Vec<usize> {
+        if !self.is_row_complete(row) {
+            return vec![];
+        }
+        let color = self.working_color[row];
+        let mut columns = self.wall_columns(row, color);
+        columns.retain(|&col| {
+            (0..self.num_rows()).all(|r| {
+                r == row
+                    || self.placement(r) != Some(col)
+                    || self.working_color[r] != color
+            })
+        });
+        columns
+    }
+
+    /// Returns the first completed row that still needs a column chosen.
+    /// Only free-placement walls have choices to make.
+    pub fn pending_placement(&self) -> Option<usize> {
+        self.free_wall.as_ref()?;
+        (0..self.num_rows()).find(|&row| {
+            self.placement(row).is_none()
+                && !self.placement_options(row).is_empty()
+        })
+    }
+
+    /// Chooses the wall column for the first row that needs one.
+    pub fn place_tile(
+        &mut self,
+        row: usize,
+        column: usize,
+    ) -> Result<(), String> {
+        if self.pending_placement() != Some(row) {
+            return Err(format!(
+                "row {} isn't waiting for a placement",
+                row + 1
+            ));
+        }
+        if !self.placement_options(row).contains(&column) {
+            return Err(format!(
+                "{:?} can't be placed in column {} of row {}",
+                self.working_color[row],
+                column + 1,
+                row + 1
+            ));
+        }
+        self.set_placement(row, Some(column));
+        self.place_forced_tiles();
+        Ok(())
+    }
+
+    /// Fills in placements that have only one choice, stopping at the first
+    /// row that needs a decision.
+    pub fn place_forced_tiles(&mut self) {
+        while let Some(row) = self.pending_placement() {
+            match self.placement_options(row)[..] {
+                [column] => self.set_placement(row, Some(column)),
+                _ => break,
+            }
+        }
+    }
+
+    /// Returns the column where a completed row scores the most points now.
+    pub fn best_placement(&self, row: usize) -> Option<usize> {
+        self.placement_options(row).into_iter().max_by_key(|&col| {
+            let mut grid = self.played_tiles.clone();
+            grid[row][col] = true;
+            score_tile(&grid, row, col)
+        })
     }
 
     pub fn add_tiles(
@@ -94,11 +360,25 @@ impl PlayerState {
         color: Color,
         num_tiles: usize,
     ) -> Result<(), String> {
-        if row == 5 {
+        self.add_tiles_with_wilds(row, color, num_tiles, 0)
+    }
+
+    /// Adds tiles of one color, plus wild tiles standing in for it. Wild
+    /// tiles fill the row after the colored ones.
+    pub fn add_tiles_with_wilds(
+        &mut self,
+        row: usize,
+        color: Color,
+        num_tiles: usize,
+        num_wilds: usize,
+    ) -> Result<(), String> {
+        let num_rows = self.num_rows();
+        if row == num_rows {
             self.send_to_trash(color, num_tiles);
+            self.send_to_trash(Color::Wild, num_wilds);
             return Ok(());
         }
-        if row > 5 {
+        if row > num_rows {
             return Err("invalid row".to_string());
         }
         if color == Color::Start {
@@ -111,6 +391,12 @@ impl PlayerState {
                 color, row_number
             ));
         }
+        if self.wall_columns(row, color).is_empty() {
+            return Err(format!(
+                "no column left for color {:?} in row {}",
+                color, row_number
+            ));
+        }
         let w_count = self.working_count[row];
         if w_count >= row_number {
             return Err(format!("no room left in row {}", row_number));
@@ -125,30 +411,57 @@ impl PlayerState {
             }
             *w_color = color;
         }
-        self.working_count[row] += if num_tiles + w_count > row_number {
-            let num_trashed = num_tiles + w_count - row_number;
-            self.send_to_trash(color, num_trashed);
-            num_tiles - num_trashed
-        } else {
-            num_tiles
-        };
+        let room = row_number - w_count;
+        let kept = num_tiles.min(room);
+        let kept_wilds = num_wilds.min(room - kept);
+        self.send_to_trash(color, num_tiles - kept);
+        self.send_to_trash(Color::Wild, num_wilds - kept_wilds);
+        self.working_count[row] += kept + kept_wilds;
+        self.working_wilds.resize(num_rows, 0);
+        self.working_wilds[row] += kept_wilds;
         Ok(())
     }
 
-    pub fn score_round(&mut self) -> Result<Vec<Color>, String> {
-        let mut round_score: i32 = 0;
+    pub fn score_round(
+        &mut self,
+        rules: &RuleSet,
+    ) -> Result<Vec<Color>, String> {
+        let mut round = RoundScore::default();
         let mut returned_tiles = vec![];
         // move completed working rows into the play grid
-        for row in 0..5 {
+        for row in 0..self.num_rows() {
             let count = self.working_count[row];
             if count <= row {
                 continue;
             }
             let color = self.working_color[row];
-            for _i in 1..count {
+            let wilds = self.working_wilds(row);
+            let column = if self.free_wall.is_some() {
+                // Rows left without a choice go where they score the most.
+                match self.placement(row).or(self.best_placement(row)) {
+                    Some(column) => column,
+                    None => {
+                        // No column is left for this color, so the whole
+                        // row goes to the floor.
+                        self.send_to_trash(color, count - wilds);
+                        self.send_to_trash(Color::Wild, wilds);
+                        self.clear_working_row(row);
+                        continue;
+                    }
+                }
+            } else {
+                played_column(row, color, self.num_rows())
+            };
+            // A colored tile goes on the wall if there is one, so wild tiles
+            // go back to the box lid.
+            let colored = count - wilds;
+            for _i in 1..colored {
                 returned_tiles.push(color);
             }
-            let column = played_column(row, color);
+            let wilds_returned = if colored == 0 { wilds - 1 } else { wilds };
+            for _i in 0..wilds_returned {
+                returned_tiles.push(Color::Wild);
+            }
             let is_played = &mut self.played_tiles[row][column];
             if *is_played {
                 return Err(format!(
@@ -158,29 +471,52 @@ impl PlayerState {
                 ));
             }
             *is_played = true;
+            if let Some(wall) = &mut self.free_wall {
+                wall[row][column] = color;
+            }
             // score the newly-played tile
-            round_score += score_tile(&self.played_tiles, row, column);
-            self.working_color[row] = Color::Blank;
-            self.working_count[row] = 0;
+            let (horiz, vert) = tile_runs(&self.played_tiles, row, column);
+            round.tiles.push(TileScore {
+                row,
+                column,
+                color,
+                horizontal: if horiz > 1 { horiz } else { 0 },
+                vertical: if vert > 1 { vert } else { 0 },
+                points: score_tile(&self.played_tiles, row, column),
+            });
+            self.clear_working_row(row);
         }
         // process trashed tiles
         for (idx, color) in self.trashed_tiles.iter().enumerate() {
-            if idx < PENALTIES.len() {
-                round_score += PENALTIES[idx];
-            }
+            round.penalties.push(FloorPenalty {
+                slot: idx,
+                color: *color,
+                points: rules.penalty(idx),
+            });
             if *color != Color::Start {
                 returned_tiles.push(*color);
             }
         }
         self.trashed_tiles.clear();
         let prev_score = self.score();
-        if prev_score + round_score < 0 {
-            round_score = -prev_score;
+        round.total = round.raw_total();
+        if prev_score + round.total < 0 {
+            round.clamp = -prev_score - round.total;
+            round.total = -prev_score;
         }
-        self.scores.push(round_score);
+        self.ledger.rounds.push(round);
         Ok(returned_tiles)
     }
 
+    fn clear_working_row(&mut self, row: usize) {
+        self.working_color[row] = Color::Blank;
+        self.working_count[row] = 0;
+        if let Some(wilds) = self.working_wilds.get_mut(row) {
+            *wilds = 0;
+        }
+        self.set_placement(row, None);
+    }
+
     pub fn num_full_rows(&self) -> i32 {
         self.played_tiles
             .iter()
@@ -188,40 +524,65 @@ impl PlayerState {
             .sum()
     }
 
-    fn num_full_columns(&self) -> i32 {
-        let mut count = 0;
-        for col in 0..5 {
-            if (0..5).all(|i| self.played_tiles[i][col]) {
-                count += 1;
-            }
-        }
-        count
+    pub fn num_full_columns(&self) -> i32 {
+        self.full_columns().len() as i32
     }
 
-    fn num_full_colors(&self) -> i32 {
-        let mut bincount = [0usize; 5];
-        for (i, row) in self.played_tiles.iter().enumerate() {
-            for (j, is_played) in row.iter().enumerate() {
-                if *is_played {
-                    bincount[played_color(i, j)] += 1;
+    pub fn num_full_colors(&self) -> i32 {
+        self.full_colors().len() as i32
+    }
+
+    fn full_rows(&self) -> Vec<usize> {
+        (0..self.num_rows())
+            .filter(|&row| self.played_tiles[row].iter().all(|p| *p))
+            .collect()
+    }
+
+    fn full_columns(&self) -> Vec<usize> {
+        let size = self.num_rows();
+        (0..size)
+            .filter(|&col| (0..size).all(|i| self.played_tiles[i][col]))
+            .collect()
+    }
+
+    fn full_colors(&self) -> Vec<Color> {
+        let size = self.num_rows();
+        let mut bincount = vec![0usize; size];
+        for i in 0..size {
+            for j in 0..size {
+                if let Some(c) = self.wall_color(i, j) {
+                    bincount[c as usize] += 1;
                 }
             }
         }
-        bincount.iter().filter(|&c| *c == 5).count() as i32
+        (0..size)
+            .filter(|&c| bincount[c] == size)
+            .map(|c| Color::try_from(c).unwrap())
+            .collect()
     }
 
-    pub fn score_bonuses(&mut self) {
-        self.scores.push(ROW_BONUS * self.num_full_rows());
-        self.scores.push(COL_BONUS * self.num_full_columns());
-        self.scores.push(KIND_BONUS * self.num_full_colors());
+    pub fn score_bonuses(&mut self, rules: &RuleSet) {
+        for row in self.full_rows() {
+            let points = rules.row_bonus;
+            self.ledger.bonuses.push(Bonus::Row { row, points });
+        }
+        for column in self.full_columns() {
+            let points = rules.column_bonus;
+            self.ledger.bonuses.push(Bonus::Column { column, points });
+        }
+        for color in self.full_colors() {
+            let points = rules.color_bonus;
+            self.ledger.bonuses.push(Bonus::Color { color, points });
+        }
     }
 
     pub fn valid_moves(&self, c: Color) -> Vec<usize> {
-        let mut result = vec![5]; // trashing is always valid
-        for row in 0..5 {
+        // trashing is always valid
+        let mut result = vec![self.num_rows()];
+        for row in 0..self.num_rows() {
             let w_color = self.working_color[row];
             if (w_color == c || w_color == Color::Blank)
-                && !self.is_played(row, c)
+                && !self.wall_columns(row, c).is_empty()
                 && self.working_count[row] <= row
             {
                 result.push(row);
@@ -231,6 +592,20 @@ impl PlayerState {
     }
 }
 
+#[cfg(test)]
+impl PlayerState {
+    pub(crate) fn set_score_for_test(&mut self, score: i32, full_rows: usize) {
+        self.ledger.clear();
+        self.ledger.rounds.push(RoundScore {
+            total: score,
+            ..Default::default()
+        });
+        for (i, row) in self.played_tiles.iter_mut().enumerate() {
+            *row = vec![i < full_rows; row.len()];
+        }
+    }
+}
+
 #[test]
 fn scoring_kind_bonuses() {
     let mut p = PlayerState::new("jim");
@@ -239,8 +614,14 @@ fn scoring_kind_bonuses() {
     }
     p.played_tiles[0][1] = true;
     assert_eq!(p.num_full_colors(), 1);
-    p.score_bonuses();
-    assert_eq!(p.scores, vec![0, 0, KIND_BONUS]);
+    p.score_bonuses(&RuleSet::default());
+    assert_eq!(
+        p.ledger.bonuses,
+        vec![Bonus::Color {
+            color: Color::Blue,
+            points: RuleSet::default().color_bonus
+        }]
+    );
 
     p.played_tiles[0][0] = false;
     assert_eq!(p.num_full_colors(), 0);
@@ -254,48 +635,75 @@ fn scoring_column_bonuses() {
     }
     p.played_tiles[0][0] = true;
     assert_eq!(p.num_full_columns(), 1);
-    p.score_bonuses();
-    assert_eq!(p.scores, vec![0, COL_BONUS, 0]);
+    p.score_bonuses(&RuleSet::default());
+    assert_eq!(
+        p.ledger.bonuses,
+        vec![Bonus::Column {
+            column: 3,
+            points: RuleSet::default().column_bonus
+        }]
+    );
 }
 
 #[test]
 fn scoring_row_bonuses() {
     let mut p = PlayerState::new("harry");
-    p.score_bonuses();
-    assert_eq!(p.scores, vec![0, 0, 0]);
+    p.score_bonuses(&RuleSet::default());
+    assert_eq!(p.ledger.bonuses, vec![]);
 
-    p.scores.clear();
     for j in 0..5 {
         p.played_tiles[1][j] = true;
     }
     p.played_tiles[0][0] = true;
     assert_eq!(p.num_full_rows(), 1);
-    p.score_bonuses();
-    assert_eq!(p.scores, vec![ROW_BONUS, 0, 0]);
+    p.score_bonuses(&RuleSet::default());
+    assert_eq!(
+        p.ledger.bonuses,
+        vec![Bonus::Row {
+            row: 1,
+            points: RuleSet::default().row_bonus
+        }]
+    );
+    assert_eq!(p.score(), RuleSet::default().row_bonus);
 }
 
 #[test]
 fn scoring_round() {
     let mut p = PlayerState::new("tom");
-    assert_eq!(p.score_round(), Ok(vec![]));
-    assert_eq!(p.scores, vec![0]);
+    assert_eq!(p.score_round(&RuleSet::default()), Ok(vec![]));
+    assert_eq!(p.ledger.round_totals(), vec![0]);
 
     assert_eq!(p.add_tiles(0, Color::Blue, 1), Ok(()));
-    assert_eq!(p.score_round(), Ok(vec![]));
-    assert_eq!(p.scores, vec![0, 1]);
+    assert_eq!(p.score_round(&RuleSet::default()), Ok(vec![]));
+    assert_eq!(p.ledger.round_totals(), vec![0, 1]);
 
     assert_eq!(p.add_tiles(1, Color::Purple, 2), Ok(()));
-    assert_eq!(p.score_round(), Ok(vec![Color::Purple]));
-    assert_eq!(p.scores, vec![0, 1, 2]);
+    assert_eq!(p.score_round(&RuleSet::default()), Ok(vec![Color::Purple]));
+    assert_eq!(p.ledger.round_totals(), vec![0, 1, 2]);
     assert_eq!(p.score(), 3);
+    assert_eq!(
+        p.ledger.rounds[2].tiles,
+        vec![TileScore {
+            row: 1,
+            column: played_column(1, Color::Purple, 5),
+            color: Color::Purple,
+            horizontal: 0,
+            vertical: 2,
+            points: 2,
+        }]
+    );
 
     assert_eq!(p.add_tiles(0, Color::Red, 5), Ok(()));
     assert_eq!(
-        p.score_round(),
+        p.score_round(&RuleSet::default()),
         Ok(vec![Color::Red, Color::Red, Color::Red, Color::Red])
     );
-    assert_eq!(p.scores, vec![0, 1, 2, -3]);
+    assert_eq!(p.ledger.round_totals(), vec![0, 1, 2, -3]);
     assert_eq!(p.score(), 0);
+    let last = &p.ledger.rounds[3];
+    assert_eq!(last.penalties.len(), 4);
+    assert_eq!(last.raw_total(), -5);
+    assert_eq!(last.clamp, 2);
 }
 
 #[test]
@@ -334,9 +742,136 @@ fn adding_tiles() {
         p.add_tiles(4, Color::Purple, 1),
         Err("working row 5 is locked to color Green".to_string())
     );
-    p.played_tiles[1][played_column(1, Color::Orange)] = true;
+    p.played_tiles[1][played_column(1, Color::Orange, 5)] = true;
     assert_eq!(
         p.add_tiles(1, Color::Orange, 2),
         Err("color Orange has already been played in row 2".to_string())
     );
 }
+
+#[test]
+fn placing_on_a_free_wall() {
+    let mut p = PlayerState::for_rules("ann", &RuleSet::gray());
+    assert_eq!(p.add_tiles(0, Color::Blue, 1), Ok(()));
+    assert_eq!(p.add_tiles(1, Color::Blue, 2), Ok(()));
+    assert_eq!(p.placement_options(0), vec![0, 1, 2, 3, 4]);
+    assert_eq!(p.pending_placement(), Some(0));
+    assert!(p.place_tile(1, 0).is_err());
+    assert_eq!(p.place_tile(0, 3), Ok(()));
+    // Blue can't be in the same column twice.
+    assert_eq!(p.placement_options(1), vec![0, 1, 2, 4]);
+    assert!(p.place_tile(1, 3).is_err());
+    assert_eq!(p.place_tile(1, 2), Ok(()));
+    assert_eq!(p.pending_placement(), None);
+
+    assert_eq!(p.score_round(&RuleSet::default()), Ok(vec![Color::Blue]));
+    assert_eq!(p.wall_color(0, 3), Some(Color::Blue));
+    assert_eq!(p.wall_color(1, 2), Some(Color::Blue));
+    assert_eq!(p.wall_color(1, 3), None);
+    assert!(p.valid_moves(Color::Blue).iter().all(|&row| row > 1));
+
+    // Once only one column is left, it is chosen automatically.
+    for row in 0..4 {
+        p.played_tiles[4][row] = true;
+    }
+    assert_eq!(p.add_tiles(4, Color::Red, 5), Ok(()));
+    p.place_forced_tiles();
+    assert_eq!(p.pending_placement(), None);
+    assert_eq!(p.placement(4), Some(4));
+}
+
+#[test]
+fn unplaceable_rows_go_to_the_floor() {
+    let mut p = PlayerState::for_rules("bob", &RuleSet::gray());
+    assert_eq!(p.add_tiles(1, Color::Green, 2), Ok(()));
+    // Fill the rest of the row, then block the last column for green.
+    if let Some(wall) = &mut p.free_wall {
+        let others = [Color::Blue, Color::Orange, Color::Red, Color::Purple];
+        for (col, color) in others.into_iter().enumerate() {
+            wall[1][col] = color;
+            p.played_tiles[1][col] = true;
+        }
+        wall[0][4] = Color::Green;
+        p.played_tiles[0][4] = true;
+    }
+    assert!(p.placement_options(1).is_empty());
+    assert_eq!(p.pending_placement(), None);
+    assert_eq!(
+        p.score_round(&RuleSet::default()),
+        Ok(vec![Color::Green, Color::Green])
+    );
+    assert_eq!(p.ledger.rounds[0].penalties.len(), 2);
+    assert_eq!(p.working_count(1), 0);
+}
+
+#[test]
+fn adding_wild_tiles() {
+    let mut p = PlayerState::new("eve");
+    assert_eq!(p.add_tiles_with_wilds(1, Color::Red, 2, 1), Ok(()));
+    assert_eq!(p.trashed_tiles, vec![Color::Wild]);
+    assert_eq!(p.add_tiles_with_wilds(2, Color::Blue, 1, 1), Ok(()));
+    assert_eq!(p.working_count(2), 2);
+    assert_eq!(p.add_tiles(2, Color::Blue, 1), Ok(()));
+    assert_eq!(p.add_tiles_with_wilds(0, Color::Green, 0, 1), Ok(()));
+    // The wild tile is returned, and a colored tile goes on the wall.
+    assert_eq!(
+        p.score_round(&RuleSet::default()),
+        Ok(vec![Color::Red, Color::Blue, Color::Wild, Color::Wild])
+    );
+    assert_eq!(
+        p.wall_color(2, played_column(2, Color::Blue, 5)),
+        Some(Color::Blue)
+    );
+    // A row of only wild tiles keeps one on the wall as the chosen color.
+    assert_eq!(
+        p.wall_color(0, played_column(0, Color::Green, 5)),
+        Some(Color::Green)
+    );
+}
+
+#[test]
+fn six_color_walls() {
+    let rules = RuleSet {
+        six_colors: true,
+        ..Default::default()
+    };
+    let mut p = PlayerState::for_rules("fay", &rules);
+    assert_eq!(p.num_rows(), 6);
+    assert_eq!(p.valid_moves(Color::Yellow), vec![6, 0, 1, 2, 3, 4, 5]);
+    for row in 0..6 {
+        assert_eq!(p.add_tiles(row, Color::Yellow, row + 1), Ok(()));
+    }
+    assert_eq!(p.score_round(&rules).map(|t| t.len()), Ok(15));
+    assert_eq!(p.full_colors(), vec![Color::Yellow]);
+    assert_eq!(p.add_tiles(6, Color::Blue, 1), Ok(()));
+    assert!(p.add_tiles(7, Color::Blue, 1).is_err());
+}
+
+#[test]
+fn handicaps() {
+    let mut p = PlayerState::for_rules("gil", &RuleSet::gray());
+    let handicap = Handicap {
+        start_score: 5,
+        wall_tiles: vec![(0, 0), (1, 1)],
+        ..Default::default()
+    };
+    assert!(
+        p.set_handicap(Handicap {
+            wall_tiles: vec![(5, 0)],
+            ..Default::default()
+        })
+        .is_err()
+    );
+    assert_eq!(p.set_handicap(handicap), Ok(()));
+    assert_eq!(p.score(), 5);
+    assert_eq!(p.wall_color(1, 1), Some(Color::Blue));
+    // Both pre-placed tiles are blue, so blue can't go in their columns.
+    assert!(p.add_tiles(0, Color::Blue, 1).is_err());
+    assert_eq!(p.add_tiles(2, Color::Blue, 3), Ok(()));
+    assert_eq!(p.placement_options(2), vec![2, 3, 4]);
+
+    let json = serde_json::to_string(&p).unwrap();
+    assert!(json.contains(r#""handicap":{"start_score":5,"#));
+    let json = serde_json::to_string(&PlayerState::new("hal")).unwrap();
+    assert!(!json.contains("handicap"));
+}
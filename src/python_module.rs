@@ -2,6 +2,7 @@
 use crate::agent::{create_agent, Agent};
 use crate::game_state;
 use crate::player_move;
+use crate::render::Painter;
 use cpython::exc::ValueError;
 use cpython::{py_class, py_module_initializer, PyErr, PyResult};
 use std::cell::RefCell;
@@ -12,7 +13,8 @@ py_class!(class BlauState |py| {
     def __new__(_cls, names: Vec<String>) -> PyResult<BlauState> {
         let name_refs: Vec<&str> = names.iter().map(String::as_str).collect();
         let mut rng = rand::rng();
-        let wrapped = game_state::GameState::new(&name_refs, &mut rng);
+        let wrapped = game_state::GameState::new(&name_refs, &mut rng)
+            .map_err(|msg| PyErr::new::<ValueError, _>(py, msg))?;
         BlauState::create_instance(py, RefCell::new(wrapped))
     }
     def start_round(&self) -> PyResult<Option<i32>> {
@@ -30,7 +32,11 @@ py_class!(class BlauState |py| {
             .map_err(|msg| PyErr::new::<ValueError, _>(py, msg))
     }
     def __str__(&self) -> PyResult<String> {
-        Ok(format!("{:?}", self.gs(py).borrow()))
+        Ok(self.gs(py).borrow().to_string())
+    }
+    def render(&self, ansi: bool = false) -> PyResult<String> {
+        let lines = Painter { ansi }.game(&self.gs(py).borrow());
+        Ok(lines.join("\n"))
     }
     def to_json(&self) -> PyResult<String> {
         serde_json::to_string(self.gs(py))
@@ -56,7 +62,9 @@ py_class!(class BlauMove |py| {
         let color = cidx.try_into().map_err(
             |_| PyErr::new::<ValueError, _>(py, "Invalid color")
         )?;
-        let wrapped = player_move::Move { factory_idx, color, working_row };
+        let wrapped = player_move::Move {
+            factory_idx, color, working_row, num_wilds: 0
+        };
         BlauMove::create_instance(py, wrapped)
     }
     @property def factory_idx(&self) -> PyResult<usize> {
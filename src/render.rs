@@ -0,0 +1,214 @@
+use crate::colors::Color;
+use crate::game_state::GameState;
+use crate::player_state::PlayerState;
+use crate::rules::RuleSet;
+use std::fmt;
+
+/// The letter a tile is drawn with.
+pub fn tile_letter(c: Color) -> char {
+    match c {
+        Color::Blue => 'B',
+        Color::Orange => 'O',
+        Color::Green => 'G',
+        Color::Red => 'R',
+        Color::Purple => 'P',
+        Color::Yellow => 'Y',
+        Color::Start => '1',
+        Color::Blank => '.',
+        Color::Wild => '*',
+    }
+}
+
+/// Draws boards as text, optionally with ANSI colors.
+#[derive(Debug, Clone, Copy)]
+pub struct Painter {
+    pub ansi: bool,
+}
+
+impl Painter {
+    pub fn tile(&self, c: Color) -> String {
+        let code = match c {
+            Color::Blue => "34",
+            Color::Orange => "38;5;208",
+            Color::Green => "32",
+            Color::Red => "31",
+            Color::Purple => "35",
+            Color::Yellow => "33",
+            Color::Start | Color::Wild => "1",
+            Color::Blank => "2",
+        };
+        self.paint(code, &tile_letter(c).to_string())
+    }
+
+    pub fn bold(&self, text: &str) -> String {
+        self.paint("1", text)
+    }
+
+    fn faint(&self, text: &str) -> String {
+        self.paint("2", text)
+    }
+
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.ansi {
+            format!("\x1b[{}m{}\x1b[0m", code, text)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// Draws a player's pattern lines as a staircase next to the wall, and
+    /// the floor line under its penalties. Empty wall spaces show the color
+    /// that goes there in lower case.
+    pub fn player(&self, p: &PlayerState, rules: &RuleSet) -> Vec<String> {
+        let size = p.num_rows();
+        let mut lines = vec![];
+        for row in 0..size {
+            let count = p.working_count(row);
+            let wilds = p.working_wilds(row);
+            let mut line = "  ".repeat(size - row - 1);
+            // Rows fill up from the wall side.
+            for i in (0..=row).rev() {
+                let t = match p.working_color(row) {
+                    Some(_) if i < wilds => self.tile(Color::Wild),
+                    Some(c) if i < count => self.tile(c),
+                    _ => self.faint("."),
+                };
+                line += &t;
+                line.push(' ');
+            }
+            line.push('|');
+            for col in 0..size {
+                let t = match (p.wall_color(row, col), p.wall_pattern(row, col))
+                {
+                    (Some(c), _) => self.tile(c),
+                    (None, Some(c)) => self.faint(
+                        &tile_letter(c).to_ascii_lowercase().to_string(),
+                    ),
+                    (None, None) => self.faint("."),
+                };
+                line.push(' ');
+                line += &t;
+            }
+            lines.push(line);
+        }
+        let trash = p.trashed_tiles();
+        let slots = rules.penalties.len().max(trash.len());
+        let mut penalties = String::from("floor");
+        let mut tiles = String::from("     ");
+        for slot in 0..slots {
+            penalties += &self.faint(&format!("{:>3}", rules.penalty(slot)));
+            tiles += "  ";
+            tiles += &match trash.get(slot) {
+                Some(&c) => self.tile(c),
+                None => self.faint("."),
+            };
+        }
+        lines.push(penalties);
+        lines.push(tiles);
+        lines
+    }
+
+    /// Draws the factories, the center and every player's board.
+    pub fn game(&self, game: &GameState) -> Vec<String> {
+        let mut lines = vec![format!("Round {}", game.round_number())];
+        for (idx, factory) in game.factories().iter().enumerate() {
+            let tiles: Vec<String> =
+                factory.iter().map(|&c| self.tile(c)).collect();
+            lines.push(format!("  Factory {}: {}", idx + 1, tiles.join(" ")));
+        }
+        let mut center: Vec<(Color, usize)> =
+            game.center().iter().map(|(&c, &n)| (c, n)).collect();
+        center.sort_by_key(|&(c, _)| c as usize);
+        let center: Vec<String> = center
+            .into_iter()
+            .flat_map(|(c, n)| vec![self.tile(c); n])
+            .collect();
+        lines.push(format!("  Center:    {}", center.join(" ")));
+        for (idx, p) in game.players.iter().enumerate() {
+            let marker = if idx == game.curr_player_idx {
+                '>'
+            } else {
+                ' '
+            };
+            let name =
+                format!("{} {} ({} points)", marker, p.display_name, p.score());
+            lines.push(String::new());
+            lines.push(self.bold(&name));
+            for line in self.player(p, game.rules()) {
+                lines.push(format!("    {}", line));
+            }
+        }
+        lines
+    }
+}
+
+fn write_lines(f: &mut fmt::Formatter, lines: &[String]) -> fmt::Result {
+    for line in lines {
+        writeln!(f, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Draws the board as text. The alternate form (`{:#}`) adds ANSI colors.
+impl fmt::Display for GameState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let painter = Painter {
+            ansi: f.alternate(),
+        };
+        write_lines(f, &painter.game(self))
+    }
+}
+
+/// Draws the player's board as text, with the standard floor penalties. The
+/// alternate form (`{:#}`) adds ANSI colors.
+impl fmt::Display for PlayerState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let painter = Painter {
+            ansi: f.alternate(),
+        };
+        writeln!(f, "{} ({} points)", self.display_name, self.score())?;
+        write_lines(f, &painter.player(self, &RuleSet::default()))
+    }
+}
+
+#[test]
+fn drawing_players() {
+    let mut p = PlayerState::new("ann");
+    p.add_tiles(1, Color::Red, 1).unwrap();
+    p.add_tiles(0, Color::Blue, 1).unwrap();
+    p.score_round(&RuleSet::default()).unwrap();
+    p.add_tiles(4, Color::Green, 3).unwrap();
+    let expected = "\
+ann (1 points)
+        . | B o g r p
+      . R | p b o g r
+    . . . | r p b o g
+  . . . . | g r p b o
+. . G G G | o g r p b
+floor -1 -1 -2 -2 -2 -3 -3
+       .  .  .  .  .  .  .
+";
+    assert_eq!(p.to_string(), expected);
+    p.send_to_trash(Color::Orange, 2);
+    assert!(p.to_string().ends_with("\n       O  O  .  .  .  .  .\n"));
+    // Colors are only added on request.
+    assert!(format!("{:#}", p).contains("\x1b[32mG\x1b[0m"));
+    assert!(!p.to_string().contains('\x1b'));
+}
+
+#[test]
+fn drawing_games() {
+    use rand::SeedableRng;
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+    let mut game = GameState::new(&["ann", "bob"], &mut rng).unwrap();
+    game.start_round();
+    let text = game.to_string();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "Round 1");
+    assert!(lines[1].starts_with("  Factory 1: "));
+    assert_eq!(lines[6], "  Center:    1");
+    let current =
+        format!("> {} (0 points)", game.current_player().display_name);
+    assert!(lines.contains(&current.as_str()));
+    assert_eq!(text.matches("floor").count(), 2);
+}
@@ -0,0 +1,271 @@
+use crate::colors::{ALL_COLORS, Color};
+use serde::{Deserialize, Serialize};
+
+pub const MIN_PLAYERS: usize = 2;
+pub const MAX_PLAYERS: usize = 4;
+
+/// How many factories there are, and how many tiles each one holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FactorySetup {
+    // Defaults to two per player, plus one.
+    pub num_factories: Option<usize>,
+    pub factory_size: usize,
+}
+
+impl Default for FactorySetup {
+    fn default() -> Self {
+        Self {
+            num_factories: None,
+            factory_size: 4,
+        }
+    }
+}
+
+impl FactorySetup {
+    pub fn num_factories(&self, num_players: usize) -> usize {
+        self.num_factories.unwrap_or(num_players * 2 + 1)
+    }
+}
+
+/// Advantages that even out games between players of different skill.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Handicap {
+    // Points the player starts the game with.
+    pub start_score: i32,
+    // Wall spaces that are tiled before the first round, as (row, column)
+    // pairs. These tiles don't come from the bag.
+    pub wall_tiles: Vec<(usize, usize)>,
+    // For bots, the number of moves to look ahead from. None is the usual
+    // number for the bot's level.
+    pub search_budget: Option<usize>,
+    // For bots, the chance of playing a random move instead of their best.
+    pub blunder_percent: u32,
+}
+
+impl Handicap {
+    pub fn is_none(&self) -> bool {
+        *self == Handicap::default()
+    }
+
+    /// True if the handicap changes how a bot plays.
+    pub fn limits_bot(&self) -> bool {
+        self.search_budget.is_some() || self.blunder_percent > 0
+    }
+}
+
+/// How wild tiles are dealt and taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WildRules {
+    // Number of wild tiles in the bag. Zero disables them.
+    pub count: usize,
+    // Most wild tiles a player can take in one move.
+    pub per_take: usize,
+    // If true, wild tiles can be taken along with tiles of a color.
+    // Otherwise they can only be taken on their own.
+    pub with_color: bool,
+}
+
+impl Default for WildRules {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            per_take: 1,
+            with_color: true,
+        }
+    }
+}
+
+/// All the numbers that can be changed for house rules and quick games.
+/// Missing fields take their values from the standard rules.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSet {
+    pub name: String,
+    pub tiles_per_color: usize,
+    pub factories: FactorySetup,
+    // Points for each floor slot, from left to right.
+    pub penalties: Vec<i32>,
+    pub row_bonus: i32,
+    pub column_bonus: i32,
+    pub color_bonus: i32,
+    // The game ends after a round in which a player has this many full rows.
+    // Zero means it only ends after `max_rounds`.
+    pub full_rows_to_end: i32,
+    // The game also ends after this many rounds, if set.
+    pub max_rounds: Option<usize>,
+    // Play on a blank wall, where players choose the column for each
+    // completed row. Each color appears once per row and column.
+    pub free_placement: bool,
+    // Adds yellow tiles and a sixth row and column to the wall.
+    pub six_colors: bool,
+    pub wilds: WildRules,
+    // Four players in two teams, with partners seated opposite each other.
+    // Partners share a combined score.
+    pub team_play: bool,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        Self {
+            name: "standard".to_string(),
+            tiles_per_color: 20,
+            factories: FactorySetup::default(),
+            penalties: vec![-1, -1, -2, -2, -2, -3, -3],
+            row_bonus: 2,
+            column_bonus: 7,
+            color_bonus: 10,
+            full_rows_to_end: 1,
+            max_rounds: None,
+            free_placement: false,
+            six_colors: false,
+            wilds: WildRules::default(),
+            team_play: false,
+        }
+    }
+}
+
+impl RuleSet {
+    /// A shorter game that ends after three rounds.
+    pub fn quick() -> Self {
+        Self {
+            name: "quick".to_string(),
+            max_rounds: Some(3),
+            ..Default::default()
+        }
+    }
+
+    /// The variant played on the gray side of the board.
+    pub fn gray() -> Self {
+        Self {
+            name: "gray".to_string(),
+            free_placement: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn preset(name: &str) -> Result<Self, String> {
+        match name {
+            "standard" => Ok(Self::default()),
+            "quick" => Ok(Self::quick()),
+            "gray" => Ok(Self::gray()),
+            _ => Err(format!("Unknown rule set: {}", name)),
+        }
+    }
+
+    /// The colors in play, which is also the size of the wall.
+    pub fn colors(&self) -> &'static [Color] {
+        if self.six_colors {
+            &ALL_COLORS
+        } else {
+            &ALL_COLORS[..5]
+        }
+    }
+
+    pub fn num_tiles(&self) -> usize {
+        self.tiles_per_color * self.colors().len() + self.wilds.count
+    }
+
+    /// Returns the penalty for a tile in the given floor slot.
+    pub fn penalty(&self, slot: usize) -> i32 {
+        self.penalties.get(slot).copied().unwrap_or(0)
+    }
+
+    /// Checks that a game with this many players can be played.
+    pub fn validate(&self, num_players: usize) -> Result<(), String> {
+        if num_players < MIN_PLAYERS {
+            return Err(format!(
+                "At least {} players are needed, got {}.",
+                MIN_PLAYERS, num_players
+            ));
+        }
+        if num_players > MAX_PLAYERS {
+            return Err(format!(
+                "At most {} players are supported, got {}.",
+                MAX_PLAYERS, num_players
+            ));
+        }
+        if self.team_play && num_players != 4 {
+            return Err(format!(
+                "Team play needs 4 players, got {}.",
+                num_players
+            ));
+        }
+        let num_factories = self.factories.num_factories(num_players);
+        let factory_size = self.factories.factory_size;
+        if num_factories == 0 || factory_size == 0 {
+            return Err("Factories must hold at least one tile.".to_string());
+        }
+        if num_factories * factory_size > self.num_tiles() {
+            return Err(format!(
+                "{} factories of {} tiles need more than the {} tiles \
+                 available.",
+                num_factories,
+                factory_size,
+                self.num_tiles()
+            ));
+        }
+        if self.wilds.count > 0 && self.wilds.per_take == 0 {
+            return Err("Wild tiles must be possible to take.".to_string());
+        }
+        if self.full_rows_to_end < 1 && self.max_rounds.is_none() {
+            return Err("The game needs a way to end.".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn parsing_partial_rules() {
+    let rules: RuleSet =
+        serde_json::from_str(r#"{"name": "lenient", "penalties": [-1]}"#)
+            .unwrap();
+    assert_eq!(rules.penalty(0), -1);
+    assert_eq!(rules.penalty(1), 0);
+    assert_eq!(rules.color_bonus, 10);
+    assert_eq!(rules.factories.factory_size, 4);
+    assert_eq!(rules.colors().len(), 5);
+
+    let rules: RuleSet =
+        serde_json::from_str(r#"{"six_colors": true, "wilds": {"count": 4}}"#)
+            .unwrap();
+    assert_eq!(rules.colors().last(), Some(&Color::Yellow));
+    assert_eq!(rules.num_tiles(), 124);
+    assert_eq!(rules.wilds.per_take, 1);
+}
+
+#[test]
+fn validating_rules() {
+    let rules = RuleSet::default();
+    assert!(rules.validate(1).is_err());
+    assert!(rules.validate(2).is_ok());
+    assert!(rules.validate(4).is_ok());
+    assert!(rules.validate(5).is_err());
+
+    let mut rules = RuleSet::quick();
+    rules.tiles_per_color = 3;
+    assert_eq!(
+        rules.validate(2).unwrap_err(),
+        "5 factories of 4 tiles need more than the 15 tiles available."
+    );
+    rules.factories.factory_size = 0;
+    assert!(rules.validate(2).is_err());
+
+    let rules = RuleSet {
+        team_play: true,
+        ..Default::default()
+    };
+    assert!(rules.validate(3).is_err());
+    assert!(rules.validate(4).is_ok());
+
+    let mut rules = RuleSet::default();
+    rules.wilds.count = 5;
+    rules.wilds.per_take = 0;
+    assert!(rules.validate(2).is_err());
+
+    assert!(RuleSet::preset("quick").is_ok());
+    assert!(RuleSet::preset("gray").unwrap().free_placement);
+    assert!(RuleSet::preset("bogus").is_err());
+}
@@ -0,0 +1,158 @@
+use crate::colors::Color;
+use serde::{Deserialize, Serialize};
+
+/// Points earned by one tile moved to the wall.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TileScore {
+    pub row: usize,
+    pub column: usize,
+    pub color: Color,
+    // Length of the horizontal run through this tile, if longer than 1.
+    pub horizontal: i32,
+    // Length of the vertical run through this tile, if longer than 1.
+    pub vertical: i32,
+    pub points: i32,
+}
+
+/// Penalty for one tile on the floor line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FloorPenalty {
+    pub slot: usize,
+    pub color: Color,
+    // Zero for tiles beyond the last penalty slot.
+    pub points: i32,
+}
+
+/// Everything scored at the end of one round.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoundScore {
+    pub tiles: Vec<TileScore>,
+    pub penalties: Vec<FloorPenalty>,
+    // Points given back so that the total score doesn't drop below zero.
+    pub clamp: i32,
+    pub total: i32,
+}
+
+impl RoundScore {
+    /// Sum of tile points and penalties, before clamping.
+    pub fn raw_total(&self) -> i32 {
+        self.tiles.iter().map(|t| t.points).sum::<i32>()
+            + self.penalties.iter().map(|p| p.points).sum::<i32>()
+    }
+}
+
+/// End-of-game bonus for a completed row, column, or color.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+#[serde(rename_all = "lowercase")]
+pub enum Bonus {
+    Row { row: usize, points: i32 },
+    Column { column: usize, points: i32 },
+    Color { color: Color, points: i32 },
+}
+
+impl Bonus {
+    pub fn points(&self) -> i32 {
+        match self {
+            Bonus::Row { points, .. } => *points,
+            Bonus::Column { points, .. } => *points,
+            Bonus::Color { points, .. } => *points,
+        }
+    }
+}
+
+/// A player's complete scoring history.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ScoreLedger {
+    pub rounds: Vec<RoundScore>,
+    pub bonuses: Vec<Bonus>,
+}
+
+impl ScoreLedger {
+    pub fn total(&self) -> i32 {
+        self.rounds.iter().map(|r| r.total).sum::<i32>()
+            + self.bonuses.iter().map(|b| b.points()).sum::<i32>()
+    }
+
+    pub fn round_totals(&self) -> Vec<i32> {
+        self.rounds.iter().map(|r| r.total).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.rounds.clear();
+        self.bonuses.clear();
+    }
+}
+
+// Stored games from before the ledger existed have a flat list of scores.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LedgerRepr {
+    Ledger {
+        rounds: Vec<RoundScore>,
+        #[serde(default)]
+        bonuses: Vec<Bonus>,
+    },
+    Legacy(Vec<i32>),
+}
+
+impl<'de> Deserialize<'de> for ScoreLedger {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(match LedgerRepr::deserialize(deserializer)? {
+            LedgerRepr::Ledger { rounds, bonuses } => {
+                ScoreLedger { rounds, bonuses }
+            }
+            // The old format can't tell rounds from bonuses, so only the
+            // totals are kept.
+            LedgerRepr::Legacy(scores) => ScoreLedger {
+                rounds: scores
+                    .into_iter()
+                    .map(|total| RoundScore {
+                        total,
+                        ..Default::default()
+                    })
+                    .collect(),
+                bonuses: vec![],
+            },
+        })
+    }
+}
+
+#[test]
+fn ledger_round_trip() {
+    let ledger = ScoreLedger {
+        rounds: vec![RoundScore {
+            tiles: vec![TileScore {
+                row: 0,
+                column: 0,
+                color: Color::Blue,
+                horizontal: 0,
+                vertical: 0,
+                points: 1,
+            }],
+            penalties: vec![FloorPenalty {
+                slot: 0,
+                color: Color::Start,
+                points: -1,
+            }],
+            clamp: 0,
+            total: 0,
+        }],
+        bonuses: vec![Bonus::Row { row: 1, points: 2 }],
+    };
+    let json = serde_json::to_string(&ledger).unwrap();
+    assert!(json.contains(r#"{"kind":"row","row":1,"points":2}"#));
+    let parsed: ScoreLedger = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed, ledger);
+    assert_eq!(parsed.total(), 2);
+}
+
+#[test]
+fn reading_legacy_scores() {
+    let parsed: ScoreLedger = serde_json::from_str("[3, 4, -1]").unwrap();
+    assert_eq!(parsed.round_totals(), vec![3, 4, -1]);
+    assert_eq!(parsed.total(), 6);
+}
@@ -0,0 +1,483 @@
+use blau_api::{DynSafeGameAPI, GameAPI, PlayerInfo, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::api::BlauAPI;
+
+#[derive(Deserialize, Serialize, Clone, Default)]
+struct MatchParams {
+    // Number of games to play, defaulting to one per player.
+    #[serde(default)]
+    num_games: Option<usize>,
+    // Whether each seat rotation is dealt the same tiles.
+    #[serde(default)]
+    duplicate: bool,
+    // Seed for the first game; later games use the following seeds.
+    #[serde(default)]
+    seed: Option<u64>,
+    // Parameters passed on to each game.
+    #[serde(default)]
+    game: Option<serde_json::Value>,
+}
+
+/// The outcome of one finished game in a match.
+#[derive(Deserialize, Serialize, Clone)]
+struct GameResult {
+    // Player IDs in seat order.
+    seats: Vec<String>,
+    seed: u64,
+    // Scores in match player order.
+    scores: Vec<i32>,
+    winners: Vec<String>,
+    // The game's own final state.
+    record: serde_json::Value,
+}
+
+/// Everything that is stored for a match, finished or not.
+#[derive(Deserialize, Serialize)]
+struct MatchRecord {
+    params: MatchParams,
+    seed: u64,
+    games: Vec<GameResult>,
+    // The game to resume at, which is the number of finished games.
+    next_game: usize,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "action")]
+enum MatchMessage<'a> {
+    #[serde(rename = "match_game_start")]
+    GameStart { game: usize, seats: &'a [String] },
+    #[serde(rename = "match_game_end")]
+    GameEnd {
+        game: usize,
+        scores: &'a [i32],
+        winners: &'a [String],
+    },
+    #[serde(rename = "match_end")]
+    End {
+        totals: Vec<i32>,
+        winners: Vec<String>,
+        games: usize,
+    },
+}
+
+/// Plays a series of games between the same players, rotating the starting
+/// seat after each game and adding up the scores.
+pub struct MatchAPI {
+    players: Vec<(String, Option<u8>)>,
+    params: MatchParams,
+    num_games: usize,
+    seed: u64,
+    games: Vec<GameResult>,
+    current: Option<BlauAPI>,
+    game_id: i64,
+}
+
+impl MatchAPI {
+    fn new(players: &[PlayerInfo], params: MatchParams) -> Result<Self> {
+        let n = players.len();
+        let num_games = params.num_games.unwrap_or(n);
+        if num_games == 0 {
+            return Err("A match needs at least one game".into());
+        }
+        if params.duplicate && !num_games.is_multiple_of(n) {
+            return Err(format!(
+                "Duplicate matches need a multiple of {n} games"
+            )
+            .into());
+        }
+        let seed = params.seed.unwrap_or_else(rand::random);
+        Ok(Self {
+            players: players.iter().map(|p| (p.id.clone(), p.level)).collect(),
+            params,
+            num_games,
+            seed,
+            games: vec![],
+            current: None,
+            game_id: 0,
+        })
+    }
+    /// Player IDs in seat order for the given game.
+    fn seats(&self, game: usize) -> Vec<String> {
+        let n = self.players.len();
+        (0..n)
+            .map(|s| self.players[(s + game) % n].0.clone())
+            .collect()
+    }
+    fn seat_info(&self, game: usize) -> Vec<PlayerInfo> {
+        let n = self.players.len();
+        (0..n)
+            .map(|s| match &self.players[(s + game) % n] {
+                (id, Some(level)) => PlayerInfo::ai(id.clone(), *level),
+                (id, None) => PlayerInfo::human(id.clone()),
+            })
+            .collect()
+    }
+    /// Duplicate matches repeat each seed once per seat rotation.
+    fn game_seed(&self, game: usize) -> u64 {
+        let offset = if self.params.duplicate {
+            game / self.players.len()
+        } else {
+            game
+        };
+        self.seed.wrapping_add(offset as u64)
+    }
+    fn create_game(&mut self) -> Result<()> {
+        let game = self.games.len();
+        let mut params = self
+            .params
+            .game
+            .clone()
+            .unwrap_or_else(|| serde_json::json!({"tutor_mode": false}));
+        params["seed"] = self.game_seed(game).into();
+        let api =
+            BlauAPI::init(&self.seat_info(game), Some(&params.to_string()))?;
+        self.current = Some(api);
+        Ok(())
+    }
+    fn broadcast<F: FnMut(&str, &str)>(
+        &self,
+        msg: &MatchMessage,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let msg = serde_json::to_string(msg)?;
+        for (id, level) in &self.players {
+            if level.is_none() {
+                notice_cb(id, &msg);
+            }
+        }
+        Ok(())
+    }
+    fn start_game<F: FnMut(&str, &str)>(
+        &mut self,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let game = self.games.len();
+        let seats = self.seats(game);
+        let msg = MatchMessage::GameStart {
+            game,
+            seats: &seats,
+        };
+        self.broadcast(&msg, &mut notice_cb)?;
+        let game_id = self.game_id;
+        let api = self.current.as_mut().ok_or("No game in progress")?;
+        api.start(game_id, &mut notice_cb)?;
+        self.advance(notice_cb)
+    }
+    /// Records finished games and moves on to the next one.
+    fn advance<F: FnMut(&str, &str)>(
+        &mut self,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        let Some(api) = &self.current else {
+            return Ok(());
+        };
+        if !api.is_game_over() {
+            return Ok(());
+        }
+        let game = self.games.len();
+        let seats = self.seats(game);
+        let seat_scores = api.player_scores();
+        let scores = self
+            .players
+            .iter()
+            .map(|(id, _)| {
+                let seat = seats.iter().position(|s| s == id).unwrap();
+                seat_scores[seat]
+            })
+            .collect();
+        let result = GameResult {
+            seats,
+            seed: self.game_seed(game),
+            scores,
+            winners: api.winner_ids(),
+            record: serde_json::from_str(&api.final_state()?)?,
+        };
+        let msg = MatchMessage::GameEnd {
+            game,
+            scores: &result.scores,
+            winners: &result.winners,
+        };
+        self.broadcast(&msg, &mut notice_cb)?;
+        self.games.push(result);
+        if self.games.len() < self.num_games {
+            self.create_game()?;
+            return self.start_game(notice_cb);
+        }
+        let msg = MatchMessage::End {
+            totals: self.totals(),
+            winners: self.winner_ids(),
+            games: self.games.len(),
+        };
+        self.broadcast(&msg, notice_cb)
+    }
+    /// Total scores of finished games, in match player order.
+    pub fn totals(&self) -> Vec<i32> {
+        let mut totals = vec![0; self.players.len()];
+        for result in &self.games {
+            for (total, score) in totals.iter_mut().zip(&result.scores) {
+                *total += score;
+            }
+        }
+        totals
+    }
+    /// Returns the IDs of all players who share the highest total.
+    pub fn winner_ids(&self) -> Vec<String> {
+        let totals = self.totals();
+        let best = totals.iter().copied().max().unwrap_or(0);
+        self.players
+            .iter()
+            .zip(&totals)
+            .filter(|&(_, &total)| total == best)
+            .map(|((id, _), _)| id.clone())
+            .collect()
+    }
+    /// Number of games finished so far.
+    pub fn games_played(&self) -> usize {
+        self.games.len()
+    }
+    /// The game in progress, if any.
+    pub fn current_game(&self) -> Option<&BlauAPI> {
+        self.current.as_ref()
+    }
+}
+
+impl GameAPI for MatchAPI {
+    fn init(players: &[PlayerInfo], params: Option<&str>) -> Result<Self> {
+        let params: MatchParams = match params {
+            Some(p) => serde_json::from_str(p)?,
+            None => MatchParams::default(),
+        };
+        let mut api = Self::new(players, params)?;
+        api.create_game()?;
+        Ok(api)
+    }
+
+    // Unfinished games can't be stored, so an unfinished match resumes at
+    // the start of its next game. Seeded games are dealt the same tiles.
+    fn restore(player_info: &[PlayerInfo], final_state: &str) -> Result<Self> {
+        let record: MatchRecord = serde_json::from_str(final_state)?;
+        let mut api = Self::new(player_info, record.params)?;
+        api.seed = record.seed;
+        api.games = record.games;
+        if api.games.len() > api.num_games {
+            return Err("Too many games in match record".into());
+        }
+        if record.next_game != api.games.len() {
+            return Err("Match record is out of step with its games".into());
+        }
+        if let Some(last) = api.games.last().filter(|_| api.is_game_over()) {
+            let game = api.games.len() - 1;
+            let record = last.record.to_string();
+            api.current =
+                Some(BlauAPI::restore(&api.seat_info(game), &record)?);
+        } else {
+            api.create_game()?;
+        }
+        Ok(api)
+    }
+
+    fn start<F: FnMut(&str, &str)>(
+        &mut self,
+        game_id: i64,
+        notice_cb: F,
+    ) -> Result<()> {
+        if self.is_game_over() {
+            return Err("Match is over".into());
+        }
+        self.game_id = game_id;
+        self.start_game(notice_cb)
+    }
+
+    fn process_action<F: FnMut(&str, &str)>(
+        &mut self,
+        action: &str,
+        mut notice_cb: F,
+    ) -> Result<()> {
+        if self.is_game_over() {
+            return Err("Match is over".into());
+        }
+        let api = self.current.as_mut().ok_or("No game in progress")?;
+        api.process_action(action, &mut notice_cb)?;
+        self.advance(notice_cb)
+    }
+}
+
+impl DynSafeGameAPI for MatchAPI {
+    fn is_game_over(&self) -> bool {
+        self.games.len() >= self.num_games
+    }
+
+    // Only finished games are recorded, so this can be saved between games.
+    fn final_state(&self) -> Result<String> {
+        let record = MatchRecord {
+            params: self.params.clone(),
+            seed: self.seed,
+            games: self.games.clone(),
+            next_game: self.games.len(),
+        };
+        Ok(serde_json::to_string(&record)?)
+    }
+
+    fn player_view(&self, player_id: &str) -> Result<String> {
+        let api = self.current.as_ref().ok_or("No game in progress")?;
+        api.player_view(player_id)
+    }
+
+    fn current_player_id(&self) -> &str {
+        match &self.current {
+            Some(api) => api.current_player_id(),
+            None => self.players[0].0.as_str(),
+        }
+    }
+
+    // Totals so far, including the game in progress.
+    fn player_scores(&self) -> Vec<i32> {
+        let mut totals = self.totals();
+        if let Some(api) =
+            self.current.as_ref().filter(|_| !self.is_game_over())
+        {
+            let seats = self.seats(self.games.len());
+            for (seat, score) in seats.iter().zip(api.player_scores()) {
+                let idx =
+                    self.players.iter().position(|(id, _)| id == seat).unwrap();
+                totals[idx] += score;
+            }
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+fn bots() -> Vec<PlayerInfo> {
+    vec![PlayerInfo::ai("a".into(), 1), PlayerInfo::ai("b".into(), 1)]
+}
+
+#[test]
+fn bot_match_finishes_at_start() {
+    let params = r#"{"num_games": 3, "seed": 7}"#;
+    let mut api: MatchAPI = GameAPI::init(&bots(), Some(params)).unwrap();
+    assert!(!api.is_game_over());
+    api.start(1, |_, _| panic!("No humans to notify")).unwrap();
+    assert!(api.is_game_over());
+    assert_eq!(api.games_played(), 3);
+    assert_eq!(api.games[0].seats, vec!["a", "b"]);
+    assert_eq!(api.games[1].seats, vec!["b", "a"]);
+    assert_eq!(api.games[2].seats, vec!["a", "b"]);
+    assert_eq!(api.games[1].seed, 8);
+    assert_eq!(api.player_scores(), api.totals());
+    assert!(api.start(1, |_, _| {}).is_err());
+}
+
+#[cfg(test)]
+fn play_game(api: &mut MatchAPI, notices: &mut Vec<String>) {
+    let game = api.games_played();
+    let agent = crate::agent::create_agent(1);
+    while api.games_played() == game {
+        let view = api.player_view(api.current_player_id()).unwrap();
+        let state: crate::game_state::GameState =
+            serde_json::from_str(&view).unwrap();
+        let mv = agent.choose_action(&state);
+        let action = crate::api::MoveMessage::from_move(&mv);
+        api.process_action(
+            &serde_json::to_string(&action).unwrap(),
+            |_, msg| notices.push(msg.to_string()),
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+fn dealt_factories(api: &MatchAPI) -> serde_json::Value {
+    let view = api.player_view("me").unwrap();
+    serde_json::from_str::<serde_json::Value>(&view).unwrap()["factories"]
+        .clone()
+}
+
+#[test]
+fn duplicate_matches_repeat_deals() {
+    let players = vec![
+        PlayerInfo::human("me".into()),
+        PlayerInfo::human("you".into()),
+    ];
+    let params = r#"{"num_games": 2, "duplicate": true, "seed": 3}"#;
+    let mut api: MatchAPI = GameAPI::init(&players, Some(params)).unwrap();
+    let mut notices = vec![];
+    api.start(1, |_, msg| notices.push(msg.to_string()))
+        .unwrap();
+    assert_eq!(
+        notices[0],
+        r#"{"action":"match_game_start","game":0,"seats":["me","you"]}"#
+    );
+    let first_deal = dealt_factories(&api);
+    let first_player = api.current_player_id().to_string();
+    notices.clear();
+    play_game(&mut api, &mut notices);
+    assert!(notices.iter().any(|m| m.contains(r#""match_game_end""#)));
+    assert!(
+        notices
+            .iter()
+            .any(|m| m.contains(r#""seats":["you","me"]"#))
+    );
+    // The second game is dealt the same tiles, with the seats swapped.
+    assert_eq!(dealt_factories(&api), first_deal);
+    assert_ne!(api.current_player_id(), first_player);
+
+    notices.clear();
+    play_game(&mut api, &mut notices);
+    assert!(api.is_game_over());
+    let last = notices.last().unwrap();
+    assert!(last.starts_with(r#"{"action":"match_end","#), "{last}");
+    assert_eq!(api.games[0].seed, api.games[1].seed);
+
+    // The number of games must cover every seat rotation.
+    let params = r#"{"num_games": 3, "duplicate": true}"#;
+    assert!(<MatchAPI as GameAPI>::init(&players, Some(params)).is_err());
+    let params = r#"{"num_games": 0}"#;
+    assert!(<MatchAPI as GameAPI>::init(&players, Some(params)).is_err());
+}
+
+#[test]
+fn restoring_matches() {
+    let players = vec![
+        PlayerInfo::human("me".into()),
+        PlayerInfo::ai("bot".into(), 1),
+    ];
+    let mut api: MatchAPI =
+        GameAPI::init(&players, Some(r#"{"num_games": 2, "seed": 1}"#))
+            .unwrap();
+    api.start(1, |_, _| {}).unwrap();
+    let saved = api.final_state().unwrap();
+    let restored: MatchAPI = GameAPI::restore(&players, &saved).unwrap();
+    assert_eq!(restored.games_played(), 0);
+    assert!(!restored.is_game_over());
+
+    // Saving between games resumes at the next one, with its seats and seed.
+    play_game(&mut api, &mut vec![]);
+    let saved = api.final_state().unwrap();
+    let mut restored: MatchAPI = GameAPI::restore(&players, &saved).unwrap();
+    assert_eq!(restored.games_played(), 1);
+    assert_eq!(restored.totals(), api.totals());
+    let mut notices = vec![];
+    restored
+        .start(1, |_, msg| notices.push(msg.to_string()))
+        .unwrap();
+    assert_eq!(
+        notices[0],
+        r#"{"action":"match_game_start","game":1,"seats":["bot","me"]}"#
+    );
+    assert_eq!(dealt_factories(&restored), dealt_factories(&api));
+    play_game(&mut restored, &mut notices);
+    assert!(restored.is_game_over());
+
+    // Finished matches restore their last game.
+    let bots = bots();
+    let mut api: MatchAPI =
+        GameAPI::init(&bots, Some(r#"{"num_games": 2}"#)).unwrap();
+    api.start(1, |_, _| {}).unwrap();
+    let saved = api.final_state().unwrap();
+    let restored: MatchAPI = GameAPI::restore(&bots, &saved).unwrap();
+    assert!(restored.is_game_over());
+    assert_eq!(restored.totals(), api.totals());
+    assert_eq!(restored.winner_ids(), api.winner_ids());
+}
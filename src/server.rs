@@ -0,0 +1,678 @@
+//! A small game host for local development, standing in for the production
+//! host of the `blau_api` traits. Clients connect over WebSocket and get the
+//! notices for their player ID. Finished games go to a `GameStore`; games
+//! in progress only live in memory.
+use blau_api::{DynSafeGameAPI, GameAPI, PlayerInfo, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tungstenite::{Message, WebSocket};
+
+use crate::api::BlauAPI;
+#[cfg(test)]
+use crate::game_state::GameState;
+
+/// A player in a hosted game. Bots have a level.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Seat {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u8>,
+}
+
+impl Seat {
+    fn info(&self) -> PlayerInfo {
+        match self.level {
+            Some(level) => PlayerInfo::ai(self.id.clone(), level),
+            None => PlayerInfo::human(self.id.clone()),
+        }
+    }
+}
+
+/// A finished game, as kept in a store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredGame {
+    pub game_id: i64,
+    pub players: Vec<Seat>,
+    pub scores: Vec<i32>,
+    pub final_state: String,
+}
+
+pub trait GameStore: Send {
+    fn save(&mut self, game: &StoredGame) -> Result<()>;
+    fn load(&self, game_id: i64) -> Result<Option<StoredGame>>;
+    fn game_ids(&self) -> Result<Vec<i64>>;
+}
+
+/// Keeps finished games until the server stops.
+#[derive(Default)]
+pub struct MemoryStore {
+    games: HashMap<i64, StoredGame>,
+}
+
+impl GameStore for MemoryStore {
+    fn save(&mut self, game: &StoredGame) -> Result<()> {
+        self.games.insert(game.game_id, game.clone());
+        Ok(())
+    }
+
+    fn load(&self, game_id: i64) -> Result<Option<StoredGame>> {
+        Ok(self.games.get(&game_id).cloned())
+    }
+
+    fn game_ids(&self) -> Result<Vec<i64>> {
+        let mut ids: Vec<i64> = self.games.keys().copied().collect();
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+/// Keeps each finished game in its own JSON file in a directory.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, game_id: i64) -> PathBuf {
+        self.dir.join(format!("{}.json", game_id))
+    }
+}
+
+impl GameStore for FileStore {
+    fn save(&mut self, game: &StoredGame) -> Result<()> {
+        std::fs::write(self.path(game.game_id), serde_json::to_string(game)?)?;
+        Ok(())
+    }
+
+    fn load(&self, game_id: i64) -> Result<Option<StoredGame>> {
+        match std::fs::read_to_string(self.path(game_id)) {
+            Ok(text) => Ok(Some(serde_json::from_str(&text)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn game_ids(&self) -> Result<Vec<i64>> {
+        let mut ids = vec![];
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let stem = path.file_stem().and_then(|s| s.to_str());
+                ids.extend(stem.and_then(|s| s.parse::<i64>().ok()));
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+}
+
+/// Messages from clients.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Request {
+    Create {
+        players: Vec<Seat>,
+        #[serde(default)]
+        params: Option<serde_json::Value>,
+    },
+    Action {
+        game_id: i64,
+        action: serde_json::Value,
+    },
+    View {
+        game_id: i64,
+    },
+    List,
+}
+
+/// Messages to clients.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Reply<'a> {
+    Created {
+        game_id: i64,
+    },
+    Notice {
+        game_id: i64,
+        message: serde_json::Value,
+    },
+    View {
+        game_id: i64,
+        view: serde_json::Value,
+    },
+    Games {
+        games: &'a [GameSummary],
+    },
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSummary {
+    pub game_id: i64,
+    pub players: Vec<Seat>,
+    pub finished: bool,
+}
+
+// Each game has its own lock, so that bots thinking in one game don't hold
+// up the others.
+struct LiveGame {
+    players: Vec<Seat>,
+    api: Mutex<BlauAPI>,
+}
+
+/// Runs games and routes their notices to connected clients. Games are only
+/// written to the store once they're finished, so games in progress are lost
+/// when the host stops.
+pub struct Host {
+    store: Mutex<Box<dyn GameStore>>,
+    games: Mutex<HashMap<i64, Arc<LiveGame>>>,
+    next_id: AtomicI64,
+    clients: Mutex<HashMap<String, Vec<Sender<String>>>>,
+}
+
+impl Host {
+    pub fn new(store: Box<dyn GameStore>) -> Result<Self> {
+        let next_id = store.game_ids()?.last().map_or(1, |id| id + 1);
+        Ok(Self {
+            store: Mutex::new(store),
+            games: Mutex::new(HashMap::new()),
+            next_id: AtomicI64::new(next_id),
+            clients: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Sends everything for `player_id` to `sender`, until it hangs up.
+    pub fn connect(&self, player_id: &str, sender: Sender<String>) {
+        self.clients
+            .lock()
+            .unwrap()
+            .entry(player_id.to_string())
+            .or_default()
+            .push(sender);
+    }
+
+    fn send(&self, player_id: &str, msg: &str) {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(senders) = clients.get_mut(player_id) {
+            senders.retain(|s| s.send(msg.to_string()).is_ok());
+        }
+    }
+
+    fn live_game(&self, game_id: i64) -> Option<Arc<LiveGame>> {
+        self.games.lock().unwrap().get(&game_id).cloned()
+    }
+
+    // Runs `f` on a live game, delivering its notices, and stores the game
+    // once it is over. Only the game itself is locked while `f` runs.
+    fn with_game<T>(
+        &self,
+        game_id: i64,
+        f: impl FnOnce(&mut BlauAPI, &mut dyn FnMut(&str, &str)) -> Result<T>,
+    ) -> Result<T> {
+        let game = self
+            .live_game(game_id)
+            .ok_or_else(|| format!("No game in progress: {}", game_id))?;
+        let mut api = game.api.lock().unwrap();
+        self.run(game_id, &game, &mut api, f)
+    }
+
+    fn run<T>(
+        &self,
+        game_id: i64,
+        game: &LiveGame,
+        api: &mut BlauAPI,
+        f: impl FnOnce(&mut BlauAPI, &mut dyn FnMut(&str, &str)) -> Result<T>,
+    ) -> Result<T> {
+        let mut notices = vec![];
+        let result = f(api, &mut |id: &str, msg: &str| {
+            notices.push((id.to_string(), msg.to_string()))
+        });
+        for (id, msg) in notices {
+            let message = serde_json::from_str(&msg)?;
+            let notice = Reply::Notice { game_id, message };
+            self.send(&id, &serde_json::to_string(&notice)?);
+        }
+        if api.is_game_over() && self.live_game(game_id).is_some() {
+            self.store.lock().unwrap().save(&StoredGame {
+                game_id,
+                players: game.players.clone(),
+                scores: api.player_scores(),
+                final_state: api.final_state()?,
+            })?;
+            self.games.lock().unwrap().remove(&game_id);
+        }
+        result
+    }
+
+    /// Creates and starts a game. `params` are passed on to `BlauAPI`.
+    /// `on_created` gets the game's ID before the game starts, so that it
+    /// can be announced ahead of the game's first notices.
+    pub fn create_game(
+        &self,
+        players: Vec<Seat>,
+        params: Option<&str>,
+        on_created: impl FnOnce(i64),
+    ) -> Result<i64> {
+        let info: Vec<PlayerInfo> = players.iter().map(Seat::info).collect();
+        let params = params.unwrap_or(r#"{"tutor_mode": false}"#);
+        let api = BlauAPI::init(&info, Some(params))?;
+        let game_id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let game = Arc::new(LiveGame {
+            players,
+            api: Mutex::new(api),
+        });
+        // Hold the game until it has started, so that nobody acts first.
+        let mut api = game.api.lock().unwrap();
+        self.games
+            .lock()
+            .unwrap()
+            .insert(game_id, Arc::clone(&game));
+        on_created(game_id);
+        self.run(game_id, &game, &mut api, |api, cb| api.start(game_id, cb))?;
+        Ok(game_id)
+    }
+
+    /// Applies an action from `player_id`. Actions that name a player or
+    /// spectator must name the sender, and moves must be theirs to make.
+    pub fn process_action(
+        &self,
+        game_id: i64,
+        player_id: &str,
+        action: &str,
+    ) -> Result<()> {
+        let value: serde_json::Value = serde_json::from_str(action)?;
+        let named = ["player_id", "spectator_id"]
+            .iter()
+            .find_map(|key| value.get(key).and_then(|v| v.as_str()));
+        self.with_game(game_id, |api, cb| {
+            match named {
+                Some(id) if id != player_id => {
+                    return Err(format!("Can't act for {}", id).into());
+                }
+                None if api.current_player_id() != player_id => {
+                    return Err("It's not your turn".into());
+                }
+                _ => {}
+            }
+            api.process_action(action, cb)
+        })
+    }
+
+    /// The game as `player_id` sees it, whether it's live or stored.
+    pub fn player_view(&self, game_id: i64, player_id: &str) -> Result<String> {
+        if let Some(game) = self.live_game(game_id) {
+            return game.api.lock().unwrap().player_view(player_id);
+        }
+        let stored = self
+            .load(game_id)?
+            .ok_or_else(|| format!("No such game: {}", game_id))?;
+        let info: Vec<PlayerInfo> =
+            stored.players.iter().map(Seat::info).collect();
+        let api = BlauAPI::restore(&info, &stored.final_state)?;
+        api.player_view(player_id)
+    }
+
+    /// A finished game from the store.
+    pub fn load(&self, game_id: i64) -> Result<Option<StoredGame>> {
+        self.store.lock().unwrap().load(game_id)
+    }
+
+    /// Lists live games, then stored ones.
+    pub fn list_games(&self) -> Result<Vec<GameSummary>> {
+        let mut games: Vec<GameSummary> = self
+            .games
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&game_id, game)| GameSummary {
+                game_id,
+                players: game.players.clone(),
+                finished: false,
+            })
+            .collect();
+        games.sort_by_key(|g| g.game_id);
+        let store = self.store.lock().unwrap();
+        for game_id in store.game_ids()? {
+            if let Some(stored) = store.load(game_id)? {
+                games.push(GameSummary {
+                    game_id,
+                    players: stored.players,
+                    finished: true,
+                });
+            }
+        }
+        Ok(games)
+    }
+
+    /// Moves for players who ran out of time, in timed games. Games that are
+    /// busy are checked next time.
+    pub fn check_timeouts(&self) {
+        let now = Instant::now();
+        let games: Vec<(i64, Arc<LiveGame>)> = self
+            .games
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(&id, game)| (id, Arc::clone(game)))
+            .collect();
+        for (game_id, game) in games {
+            let Ok(mut api) = game.api.try_lock() else {
+                continue;
+            };
+            // Errors only affect the timed-out game, which stays as it was.
+            let _ = self.run(game_id, &game, &mut api, |api, cb| {
+                api.check_timeouts(now, cb).map(|_| ())
+            });
+        }
+    }
+
+    /// Handles a client message, replying only to its sender.
+    pub fn handle(&self, player_id: &str, request: &str) {
+        let reply = match self.dispatch(player_id, request) {
+            Ok(Some(reply)) => reply.to_string(),
+            Ok(None) => return,
+            Err(e) => {
+                let message = e.to_string();
+                let error = Reply::Error { message };
+                serde_json::to_string(&error).unwrap_or_default()
+            }
+        };
+        self.send(player_id, &reply);
+    }
+
+    fn dispatch(
+        &self,
+        player_id: &str,
+        request: &str,
+    ) -> Result<Option<serde_json::Value>> {
+        let reply = match serde_json::from_str(request)? {
+            Request::Create { players, params } => {
+                let params = params.map(|p| p.to_string());
+                // Clients hear about the game before its first notices.
+                self.create_game(players, params.as_deref(), |game_id| {
+                    let created = Reply::Created { game_id };
+                    if let Ok(reply) = serde_json::to_string(&created) {
+                        self.send(player_id, &reply);
+                    }
+                })?;
+                return Ok(None);
+            }
+            Request::Action { game_id, action } => {
+                let action = action.to_string();
+                self.process_action(game_id, player_id, &action)?;
+                return Ok(None);
+            }
+            Request::View { game_id } => Reply::View {
+                game_id,
+                view: serde_json::from_str(
+                    &self.player_view(game_id, player_id)?,
+                )?,
+            },
+            Request::List => {
+                let games = self.list_games()?;
+                return Ok(Some(serde_json::to_value(Reply::Games {
+                    games: &games,
+                })?));
+            }
+        };
+        Ok(Some(serde_json::to_value(reply)?))
+    }
+}
+
+/// Accepts connections until the listener fails. WebSocket clients connect
+/// to `/ws?player=ID`. Plain HTTP requests can `GET /games` and
+/// `GET /games/ID`.
+pub fn serve(listener: TcpListener, host: Host) -> Result<()> {
+    let host = Arc::new(host);
+    let ticker = Arc::clone(&host);
+    std::thread::spawn(move || {
+        loop {
+            std::thread::sleep(Duration::from_millis(250));
+            ticker.check_timeouts();
+        }
+    });
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let host = Arc::clone(&host);
+        std::thread::spawn(move || {
+            if let Err(e) = handle_connection(stream, &host) {
+                eprintln!("Connection failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, host: &Host) -> Result<()> {
+    let mut head = [0; 2048];
+    let n = stream.peek(&mut head)?;
+    let head = String::from_utf8_lossy(&head[..n]).to_string();
+    let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+    if head.to_ascii_lowercase().contains("upgrade: websocket") {
+        let player_id = path
+            .split_once("player=")
+            .map(|(_, id)| id.split('&').next().unwrap_or_default())
+            .filter(|id| !id.is_empty())
+            .ok_or("Missing player ID")?
+            .to_string();
+        let ws = tungstenite::accept(stream).map_err(|e| e.to_string())?;
+        let (sender, receiver) = channel();
+        host.connect(&player_id, sender);
+        return run_socket(ws, receiver, &player_id, host);
+    }
+    serve_http(stream, &path, host)
+}
+
+fn run_socket(
+    mut ws: WebSocket<TcpStream>,
+    outbox: Receiver<String>,
+    player_id: &str,
+    host: &Host,
+) -> Result<()> {
+    ws.get_mut()
+        .set_read_timeout(Some(Duration::from_millis(50)))?;
+    loop {
+        while let Ok(msg) = outbox.try_recv() {
+            ws.send(Message::text(msg)).map_err(|e| e.to_string())?;
+        }
+        match ws.read() {
+            Ok(Message::Text(text)) => host.handle(player_id, text.as_str()),
+            Ok(Message::Close(_)) => return Ok(()),
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock
+                        | std::io::ErrorKind::TimedOut
+                ) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.to_string().into()),
+        }
+    }
+}
+
+fn serve_http(mut stream: TcpStream, path: &str, host: &Host) -> Result<()> {
+    // Read the request, so that closing the socket doesn't reset it.
+    let mut buf = [0; 2048];
+    let _ = stream.read(&mut buf)?;
+    let body = match path.trim_end_matches('/') {
+        "/games" => host
+            .list_games()
+            .and_then(|g| Ok(serde_json::to_string(&g)?)),
+        p => match p.strip_prefix("/games/").map(str::parse::<i64>) {
+            Some(Ok(game_id)) => match host.load(game_id) {
+                Ok(Some(stored)) => Ok(serde_json::to_string(&stored)?),
+                Ok(None) => Err(format!("No stored game: {}", game_id).into()),
+                Err(e) => Err(e),
+            },
+            _ => Err("Not found".into()),
+        },
+    };
+    let (status, body) = match body {
+        Ok(body) => ("200 OK", body),
+        Err(e) => (
+            "404 Not Found",
+            serde_json::json!({"error": e.to_string()}).to_string(),
+        ),
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+impl Host {
+    fn current_player(&self, game_id: i64) -> Option<String> {
+        let game = self.live_game(game_id)?;
+        let api = game.api.lock().unwrap();
+        Some(api.current_player_id().to_string())
+    }
+}
+
+#[cfg(test)]
+fn test_host() -> (Host, Receiver<String>) {
+    let host = Host::new(Box::new(MemoryStore::default())).unwrap();
+    let (sender, receiver) = channel();
+    host.connect("me", sender);
+    (host, receiver)
+}
+
+#[test]
+fn hosting_a_game() {
+    use crate::agent::create_agent;
+    use crate::api::MoveMessage;
+    let (host, notices) = test_host();
+    host.handle(
+        "me",
+        r#"{"type": "create", "players": [{"id": "me"}, {"id": "bot", "level": 0}],
+            "params": {"tutor_mode": false, "seed": 4}}"#,
+    );
+    let created: serde_json::Value =
+        serde_json::from_str(&notices.recv().unwrap()).unwrap();
+    assert_eq!(created["type"], "created");
+    let game_id = created["game_id"].as_i64().unwrap();
+    let start: serde_json::Value =
+        serde_json::from_str(&notices.recv().unwrap()).unwrap();
+    assert_eq!(start["type"], "notice");
+    assert_eq!(start["game_id"], game_id);
+
+    // The bot moves right after us, so it's our turn until the game ends.
+    let agent = create_agent(0);
+    while let Some(curr) = host.current_player(game_id) {
+        assert_eq!(curr, "me");
+        let view = host.player_view(game_id, "me").unwrap();
+        let state: GameState = serde_json::from_str(&view).unwrap();
+        let mv = MoveMessage::from_move(&agent.choose_action(&state));
+        let mv = serde_json::to_string(&mv).unwrap();
+        host.process_action(game_id, "me", &mv).unwrap();
+    }
+    let games = host.list_games().unwrap();
+    assert_eq!(games.len(), 1);
+    assert!(games[0].finished);
+    assert!(host.player_view(game_id, "me").is_ok());
+    assert!(notices.try_iter().any(|m| m.contains(r#""action":"end""#)));
+}
+
+#[test]
+fn rejecting_actions_for_others() {
+    use crate::api::MoveMessage;
+    let (host, notices) = test_host();
+    let players = vec![
+        Seat {
+            id: "me".into(),
+            level: None,
+        },
+        Seat {
+            id: "you".into(),
+            level: None,
+        },
+    ];
+    let game_id = host.create_game(players, None, |_| {}).unwrap();
+    let curr = host.current_player(game_id).unwrap();
+    let other = if curr == "me" { "you" } else { "me" };
+    let view = host.player_view(game_id, other).unwrap();
+    let state: GameState = serde_json::from_str(&view).unwrap();
+    let mv = MoveMessage::from_move(&state.valid_moves()[0]);
+    let mv = serde_json::to_string(&mv).unwrap();
+    assert!(host.process_action(game_id, other, &mv).is_err());
+    let resign = format!(r#"{{"action": "resign", "player_id": "{}"}}"#, curr);
+    assert!(host.process_action(game_id, other, &resign).is_err());
+    assert!(host.process_action(game_id, &curr, &mv).is_ok());
+
+    // Errors go back to the client that caused them.
+    host.handle("me", r#"{"type": "view", "game_id": 999}"#);
+    let last = notices.try_iter().last().unwrap();
+    assert_eq!(last, r#"{"type":"error","message":"No such game: 999"}"#);
+}
+
+#[test]
+fn busy_games_dont_block_others() {
+    let (host, _notices) = test_host();
+    let seats = || {
+        vec![
+            Seat {
+                id: "me".into(),
+                level: None,
+            },
+            Seat {
+                id: "bot".into(),
+                level: Some(0),
+            },
+        ]
+    };
+    let busy = host.create_game(seats(), None, |_| {}).unwrap();
+    let other = host.create_game(seats(), None, |_| {}).unwrap();
+    // As if a bot were thinking in the busy game.
+    let game = host.live_game(busy).unwrap();
+    let _thinking = game.api.lock().unwrap();
+    assert_eq!(host.list_games().unwrap().len(), 2);
+    host.check_timeouts();
+    let view = host.player_view(other, "me").unwrap();
+    let state: GameState = serde_json::from_str(&view).unwrap();
+    let mv = crate::api::MoveMessage::from_move(&state.valid_moves()[0]);
+    let mv = serde_json::to_string(&mv).unwrap();
+    host.process_action(other, "me", &mv).unwrap();
+}
+
+#[test]
+fn storing_games_in_files() {
+    let dir =
+        std::env::temp_dir().join(format!("blau-store-{}", std::process::id()));
+    let mut store = FileStore::new(&dir).unwrap();
+    let game = StoredGame {
+        game_id: 3,
+        players: vec![Seat {
+            id: "a".into(),
+            level: Some(1),
+        }],
+        scores: vec![10],
+        final_state: "{}".into(),
+    };
+    store.save(&game).unwrap();
+    assert_eq!(store.game_ids().unwrap(), vec![3]);
+    assert_eq!(store.load(3).unwrap().unwrap().scores, vec![10]);
+    assert!(store.load(4).unwrap().is_none());
+    // New games continue from the stored IDs.
+    let host = Host::new(Box::new(store)).unwrap();
+    assert_eq!(host.next_id.load(Ordering::Relaxed), 4);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
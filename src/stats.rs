@@ -0,0 +1,341 @@
+use serde::{Deserialize, Serialize};
+
+/// Which way a one-sided test looks for a difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Alternative {
+    Greater,
+    Less,
+    TwoSided,
+}
+
+/// Wilcoxon signed-rank test of whether paired differences are centered on
+/// zero. Zero differences are ranked and then dropped (Pratt's method), and
+/// the p-value uses the normal approximation with a correction for ties.
+/// Returns None if there are no nonzero differences.
+pub fn wilcoxon(diffs: &[f64], alternative: Alternative) -> Option<f64> {
+    let n = diffs.len() as f64;
+    let ranks =
+        average_ranks(&diffs.iter().map(|d| d.abs()).collect::<Vec<_>>());
+    let num_zero = diffs.iter().filter(|&&d| d == 0.0).count() as f64;
+    if num_zero == n {
+        return None;
+    }
+    let r_plus: f64 = diffs
+        .iter()
+        .zip(&ranks)
+        .filter(|&(&d, _)| d > 0.0)
+        .map(|(_, r)| r)
+        .sum();
+    let mean = n * (n + 1.0) / 4.0 - num_zero * (num_zero + 1.0) / 4.0;
+    let mut var = n * (n + 1.0) * (2.0 * n + 1.0)
+        - num_zero * (num_zero + 1.0) * (2.0 * num_zero + 1.0);
+    let nonzero: Vec<f64> = diffs
+        .iter()
+        .zip(&ranks)
+        .filter(|&(&d, _)| d != 0.0)
+        .map(|(_, &r)| r)
+        .collect();
+    for t in tie_sizes(&nonzero) {
+        var -= 0.5 * t * (t * t - 1.0);
+    }
+    let z = (r_plus - mean) / (var / 24.0).sqrt();
+    Some(match alternative {
+        Alternative::Greater => normal_sf(z),
+        Alternative::Less => normal_sf(-z),
+        Alternative::TwoSided => (2.0 * normal_sf(z.abs())).min(1.0),
+    })
+}
+
+// Ranks starting at 1, giving tied values the mean of their ranks.
+fn average_ranks(values: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut start = 0;
+    while start < order.len() {
+        let mut end = start + 1;
+        while end < order.len() && values[order[end]] == values[order[start]] {
+            end += 1;
+        }
+        let rank = (start + end + 1) as f64 / 2.0;
+        for &idx in &order[start..end] {
+            ranks[idx] = rank;
+        }
+        start = end;
+    }
+    ranks
+}
+
+// Sizes of each group of equal values, for groups larger than one.
+fn tie_sizes(values: &[f64]) -> Vec<f64> {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+        .chunk_by(|a, b| a == b)
+        .filter(|group| group.len() > 1)
+        .map(|group| group.len() as f64)
+        .collect()
+}
+
+/// The chance that a standard normal variable is above `z`.
+pub fn normal_sf(z: f64) -> f64 {
+    0.5 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+// Complementary error function, with a fractional error below 1.2e-7.
+// From Numerical Recipes, section 6.2.
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let poly = -z * z - 1.26551223
+        + t * (1.00002368
+            + t * (0.37409196
+                + t * (0.09678418
+                    + t * (-0.18628806
+                        + t * (0.27886807
+                            + t * (-1.13520398
+                                + t * (1.48851587
+                                    + t * (-0.82215223 + t * 0.17087277))))))));
+    let r = t * poly.exp();
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+/// The value a standard normal variable is below with probability `p`,
+/// using Acklam's rational approximation.
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    let tail = |q: f64| {
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    };
+    if p <= 0.0 {
+        f64::NEG_INFINITY
+    } else if p >= 1.0 {
+        f64::INFINITY
+    } else if p < 0.02425 {
+        tail((-2.0 * p.ln()).sqrt())
+    } else if p > 1.0 - 0.02425 {
+        -tail((-2.0 * (1.0 - p).ln()).sqrt())
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5])
+            * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r
+                + 1.0)
+    }
+}
+
+/// A two-sided confidence interval for the mean of `samples`, using the
+/// normal approximation. `confidence` is e.g. 0.95 for a 95% interval.
+pub fn mean_interval(samples: &[f64], confidence: f64) -> (f64, f64) {
+    let n = samples.len() as f64;
+    if samples.is_empty() {
+        return (f64::NAN, f64::NAN);
+    }
+    let mean = samples.iter().sum::<f64>() / n;
+    if samples.len() < 2 {
+        return (mean, mean);
+    }
+    let var =
+        samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0);
+    let half = normal_quantile(0.5 + confidence / 2.0) * (var / n).sqrt();
+    (mean - half, mean + half)
+}
+
+/// A Wilson score interval for the chance of success, after `successes`
+/// out of `trials`.
+pub fn proportion_interval(
+    successes: usize,
+    trials: usize,
+    confidence: f64,
+) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z = normal_quantile(0.5 + confidence / 2.0);
+    let denom = 1.0 + z * z / n;
+    let center = (p + z * z / (2.0 * n)) / denom;
+    let half = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denom;
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+// Score of the player with rank `a` in a head-to-head against rank `b`.
+fn pair_score(a: usize, b: usize) -> f64 {
+    match a.cmp(&b) {
+        std::cmp::Ordering::Less => 1.0,
+        std::cmp::Ordering::Equal => 0.5,
+        std::cmp::Ordering::Greater => 0.0,
+    }
+}
+
+/// Elo ratings for multi-player games, which count as a head-to-head
+/// result between every pair of players.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Elo {
+    pub ratings: Vec<f64>,
+    // Largest rating change from one game.
+    pub k: f64,
+}
+
+impl Elo {
+    pub fn new(num_players: usize) -> Self {
+        Self {
+            ratings: vec![1500.0; num_players],
+            k: 20.0,
+        }
+    }
+
+    /// Updates the ratings of the given players, from their ranks in one
+    /// game (1 for the winner, with ties sharing a rank).
+    pub fn update(&mut self, players: &[usize], ranks: &[usize]) {
+        let n = players.len();
+        if n < 2 {
+            return;
+        }
+        let mut deltas = vec![0.0; n];
+        for i in 0..n {
+            for j in 0..n {
+                if i == j {
+                    continue;
+                }
+                let gap = self.ratings[players[j]] - self.ratings[players[i]];
+                let expected = 1.0 / (1.0 + 10f64.powf(gap / 400.0));
+                let score = pair_score(ranks[i], ranks[j]);
+                deltas[i] += self.k * (score - expected) / (n - 1) as f64;
+            }
+        }
+        for (&p, delta) in players.iter().zip(deltas) {
+            self.ratings[p] += delta;
+        }
+    }
+}
+
+/// A skill estimate with its uncertainty, in the style of TrueSkill.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rating {
+    pub mu: f64,
+    pub sigma: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Self {
+            mu: 25.0,
+            sigma: 25.0 / 3.0,
+        }
+    }
+}
+
+impl Rating {
+    /// A skill level the player is very likely to be above.
+    pub fn conservative(&self) -> f64 {
+        self.mu - 3.0 * self.sigma
+    }
+
+    /// Updates ratings from the players' ranks in one game, using the
+    /// Bradley-Terry model of Weng and Lin (2011).
+    pub fn update(ratings: &mut [Rating], players: &[usize], ranks: &[usize]) {
+        const BETA: f64 = 25.0 / 6.0;
+        const KAPPA: f64 = 1e-4;
+        let old: Vec<Rating> = players.iter().map(|&p| ratings[p]).collect();
+        for (i, &p) in players.iter().enumerate() {
+            let mut omega = 0.0;
+            let mut delta = 0.0;
+            for (q, other) in old.iter().enumerate() {
+                if q == i {
+                    continue;
+                }
+                let var = old[i].sigma.powi(2);
+                let c = (var + other.sigma.powi(2) + 2.0 * BETA * BETA).sqrt();
+                let p_win = 1.0 / (1.0 + ((other.mu - old[i].mu) / c).exp());
+                let score = pair_score(ranks[i], ranks[q]);
+                omega += var / c * (score - p_win);
+                let gamma = old[i].sigma / c;
+                delta += gamma * var / (c * c) * p_win * (1.0 - p_win);
+            }
+            ratings[p].mu += omega;
+            ratings[p].sigma *= (1.0 - delta).max(KAPPA).sqrt();
+        }
+    }
+}
+
+#[cfg(test)]
+fn assert_close(a: f64, b: f64) {
+    assert!((a - b).abs() < 1e-4, "{a} != {b}");
+}
+
+#[test]
+fn wilcoxon_matches_scipy() {
+    // scipy.stats.wilcoxon(d, zero_method="pratt", mode="approx")
+    let d = [3.0, -1.0, 4.0, 0.0, 5.0, 2.0, -2.0, 6.0, 0.0, 3.0];
+    assert_close(wilcoxon(&d, Alternative::Greater).unwrap(), 0.0286797);
+    assert_close(wilcoxon(&d, Alternative::Less).unwrap(), 0.9713203);
+    assert_close(wilcoxon(&d, Alternative::TwoSided).unwrap(), 0.0573594);
+    assert_eq!(wilcoxon(&[0.0, 0.0], Alternative::Greater), None);
+}
+
+#[test]
+fn normal_distribution() {
+    assert_close(normal_sf(0.0), 0.5);
+    assert_close(normal_sf(1.959964), 0.025);
+    assert_close(normal_quantile(0.975), 1.959964);
+    assert_close(normal_quantile(0.01), -2.326348);
+    let (lo, hi) = mean_interval(&[1.0, 2.0, 3.0, 4.0], 0.95);
+    assert_close(lo, 1.234849);
+    assert_close(hi, 3.765151);
+    let (lo, hi) = proportion_interval(5, 10, 0.95);
+    assert_close(lo, 0.236593);
+    assert_close(hi, 0.763407);
+}
+
+#[test]
+fn ratings_favor_winners() {
+    let mut elo = Elo::new(3);
+    elo.update(&[0, 1, 2], &[1, 2, 3]);
+    assert!(elo.ratings[0] > 1500.0 && elo.ratings[2] < 1500.0);
+    assert_close(elo.ratings[1], 1500.0);
+    assert_close(elo.ratings.iter().sum::<f64>(), 4500.0);
+
+    let mut ratings = vec![Rating::default(); 2];
+    for _ in 0..10 {
+        Rating::update(&mut ratings, &[0, 1], &[1, 2]);
+    }
+    assert!(ratings[0].mu > 25.0 && ratings[1].mu < 25.0);
+    assert!(ratings[0].sigma < 25.0 / 3.0);
+    // Tied players stay level.
+    let mut ratings = vec![Rating::default(); 2];
+    Rating::update(&mut ratings, &[0, 1], &[1, 1]);
+    assert_close(ratings[0].mu, 25.0);
+}
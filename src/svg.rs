@@ -0,0 +1,329 @@
+use crate::colors::Color;
+use crate::game_state::GameState;
+use crate::player_move::Move;
+use crate::player_state::PlayerState;
+use crate::rules::RuleSet;
+use std::fmt::Write;
+
+// Size of a tile, and the distance between neighboring tiles.
+const TILE: i32 = 28;
+const STEP: i32 = 32;
+const FACTORY_RADIUS: i32 = 38;
+const MARGIN: i32 = 16;
+
+fn fill(c: Color) -> &'static str {
+    match c {
+        Color::Blue => "#2a6fdb",
+        Color::Orange => "#f28c28",
+        Color::Green => "#3aa655",
+        Color::Red => "#d7263d",
+        Color::Purple => "#7d3c98",
+        Color::Yellow => "#f4d03f",
+        Color::Start | Color::Wild | Color::Blank => "#ffffff",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Collects SVG elements, then wraps them in a document of the right size.
+struct Canvas {
+    body: String,
+    width: i32,
+    height: i32,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        Self {
+            body: String::new(),
+            width: 0,
+            height: 0,
+        }
+    }
+
+    fn grow(&mut self, x: i32, y: i32) {
+        self.width = self.width.max(x + MARGIN);
+        self.height = self.height.max(y + MARGIN);
+    }
+
+    // Draws a tile with its top left corner at (x, y).
+    fn tile(&mut self, x: i32, y: i32, c: Color) {
+        let _ = writeln!(
+            self.body,
+            r##"<rect x="{x}" y="{y}" width="{TILE}" height="{TILE}" rx="4" fill="{}" stroke="#333"/>"##,
+            fill(c)
+        );
+        let label = match c {
+            Color::Start => "1",
+            Color::Wild => "*",
+            _ => "",
+        };
+        if !label.is_empty() {
+            self.text(x + TILE / 2, y + TILE / 2 + 6, label, "middle", 16);
+        }
+        self.grow(x + TILE, y + TILE);
+    }
+
+    // Draws an empty space, tinted with the color that belongs there.
+    fn space(&mut self, x: i32, y: i32, c: Option<Color>) {
+        let (fill, opacity) = match c {
+            Some(c) => (fill(c), "0.25"),
+            None => ("#ffffff", "1"),
+        };
+        let _ = writeln!(
+            self.body,
+            r##"<rect x="{x}" y="{y}" width="{TILE}" height="{TILE}" rx="4" fill="{fill}" fill-opacity="{opacity}" stroke="#bbb"/>"##
+        );
+        self.grow(x + TILE, y + TILE);
+    }
+
+    fn text(&mut self, x: i32, y: i32, text: &str, anchor: &str, size: i32) {
+        let _ = writeln!(
+            self.body,
+            r#"<text x="{x}" y="{y}" font-family="sans-serif" font-size="{size}" text-anchor="{anchor}">{}</text>"#,
+            escape(text)
+        );
+    }
+
+    fn arrow(&mut self, from: (i32, i32), to: (i32, i32)) {
+        let _ = writeln!(
+            self.body,
+            r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#111" stroke-width="3" marker-end="url(#arrow)"/>"##,
+            from.0, from.1, to.0, to.1
+        );
+    }
+
+    fn finish(self) -> String {
+        format!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">
+<defs><marker id="arrow" viewBox="0 0 10 10" refX="9" refY="5" markerWidth="6" markerHeight="6" orient="auto-start-reverse"><path d="M 0 0 L 10 5 L 0 10 z" fill="#111"/></marker></defs>
+<rect width="100%" height="100%" fill="#f7f3ea"/>
+{}</svg>
+"##,
+            self.body,
+            w = self.width,
+            h = self.height
+        )
+    }
+}
+
+/// Where a player's board was drawn, for pointing arrows at it.
+struct BoardLayout {
+    x: i32,
+    y: i32,
+    size: i32,
+}
+
+impl BoardLayout {
+    fn width(size: i32) -> i32 {
+        2 * size * STEP + MARGIN
+    }
+
+    fn row_y(&self, row: i32) -> i32 {
+        self.y + 24 + row * STEP
+    }
+
+    fn floor_y(&self) -> i32 {
+        self.row_y(self.size) + 20
+    }
+
+    fn wall_x(&self) -> i32 {
+        self.x + self.size * STEP + MARGIN
+    }
+
+    // The point next to the wall end of a pattern line, or the floor.
+    fn target(&self, row: usize) -> (i32, i32) {
+        let row = row as i32;
+        if row >= self.size {
+            (self.x + TILE / 2, self.floor_y() + TILE / 2)
+        } else {
+            (
+                self.wall_x() - MARGIN - TILE / 2,
+                self.row_y(row) + TILE / 2,
+            )
+        }
+    }
+}
+
+fn draw_player(
+    canvas: &mut Canvas,
+    p: &PlayerState,
+    rules: &RuleSet,
+    x: i32,
+    y: i32,
+) -> BoardLayout {
+    let size = p.num_rows();
+    let layout = BoardLayout {
+        x,
+        y,
+        size: size as i32,
+    };
+    let title = format!("{} ({} points)", p.display_name, p.score());
+    canvas.text(x, y + 16, &title, "start", 16);
+    for row in 0..size {
+        let ry = layout.row_y(row as i32);
+        let count = p.working_count(row);
+        let wilds = p.working_wilds(row);
+        // Pattern lines fill up from the wall side.
+        for i in 0..=row {
+            let tx = layout.wall_x() - MARGIN - (i as i32 + 1) * STEP;
+            match p.working_color(row) {
+                Some(_) if i < wilds => canvas.tile(tx, ry, Color::Wild),
+                Some(c) if i < count => canvas.tile(tx, ry, c),
+                _ => canvas.space(tx, ry, None),
+            }
+        }
+        for col in 0..size {
+            let tx = layout.wall_x() + col as i32 * STEP;
+            match p.wall_color(row, col) {
+                Some(c) => canvas.tile(tx, ry, c),
+                None => canvas.space(tx, ry, p.wall_pattern(row, col)),
+            }
+        }
+    }
+    let trash = p.trashed_tiles();
+    let fy = layout.floor_y();
+    for slot in 0..rules.penalties.len().max(trash.len()) {
+        let tx = x + slot as i32 * STEP;
+        let penalty = rules.penalty(slot).to_string();
+        canvas.text(tx + TILE / 2, fy - 4, &penalty, "middle", 12);
+        match trash.get(slot) {
+            Some(&c) => canvas.tile(tx, fy, c),
+            None => canvas.space(tx, fy, None),
+        }
+    }
+    layout
+}
+
+/// Draws a single player's board as a standalone SVG image.
+pub fn player_svg(p: &PlayerState, rules: &RuleSet) -> String {
+    let mut canvas = Canvas::new();
+    draw_player(&mut canvas, p, rules, MARGIN, MARGIN);
+    canvas.finish()
+}
+
+/// Draws the factories, the center and every player's board as a standalone
+/// SVG image. If a move is given, an arrow shows it for the current player.
+/// The move has to be legal.
+pub fn game_svg(
+    game: &GameState,
+    annotation: Option<&Move>,
+) -> Result<String, String> {
+    if let Some(m) = annotation
+        && !game.valid_moves().contains(m)
+    {
+        return Err(format!("Can't annotate an illegal move: {:?}", m));
+    }
+    let mut canvas = Canvas::new();
+    let mut sources = vec![];
+    let cy = MARGIN + FACTORY_RADIUS;
+    for (idx, factory) in game.factories().iter().enumerate() {
+        let cx =
+            MARGIN + FACTORY_RADIUS + idx as i32 * (2 * FACTORY_RADIUS + 8);
+        let _ = writeln!(
+            canvas.body,
+            r##"<circle cx="{cx}" cy="{cy}" r="{FACTORY_RADIUS}" fill="#e8dcc2" stroke="#8a7a5a"/>"##
+        );
+        canvas.grow(cx + FACTORY_RADIUS, cy + FACTORY_RADIUS);
+        for (i, &c) in factory.iter().enumerate() {
+            let tx = cx - STEP / 2 - TILE / 2 + (i as i32 % 2) * STEP;
+            let ty = cy - STEP / 2 - TILE / 2 + (i as i32 / 2) * STEP;
+            canvas.tile(tx, ty, c);
+        }
+        sources.push((cx, cy));
+    }
+    // The center is drawn after the factories, as rows of tiles.
+    let center_x =
+        MARGIN + game.factories().len() as i32 * (2 * FACTORY_RADIUS + 8);
+    let mut center: Vec<(Color, usize)> =
+        game.center().iter().map(|(&c, &n)| (c, n)).collect();
+    center.sort_by_key(|&(c, _)| c as usize);
+    let tiles: Vec<Color> = center
+        .into_iter()
+        .flat_map(|(c, n)| std::iter::repeat_n(c, n))
+        .collect();
+    canvas.text(center_x, MARGIN + 12, "Center", "start", 14);
+    for (i, &c) in tiles.iter().enumerate() {
+        let tx = center_x + (i as i32 % 8) * STEP;
+        let ty = MARGIN + 20 + (i as i32 / 8) * STEP;
+        canvas.tile(tx, ty, c);
+    }
+    let center_rows = (tiles.len() as i32 + 7) / 8;
+    let center_point = (center_x + TILE / 2, MARGIN + 20 + TILE / 2);
+
+    let board_y =
+        (2 * FACTORY_RADIUS).max(20 + center_rows * STEP) + 2 * MARGIN;
+    let mut layouts = vec![];
+    for (idx, p) in game.players.iter().enumerate() {
+        let width = BoardLayout::width(p.num_rows() as i32);
+        let x = MARGIN + idx as i32 * (width + MARGIN);
+        layouts.push(draw_player(&mut canvas, p, game.rules(), x, board_y));
+    }
+    if let Some(m) = annotation {
+        let from = if m.is_from_center() {
+            center_point
+        } else {
+            sources[m.factory_idx - 1]
+        };
+        let to = layouts[game.curr_player_idx].target(m.working_row);
+        canvas.arrow(from, to);
+    }
+    Ok(canvas.finish())
+}
+
+// Compares output to a stored snapshot. Set BLAU_UPDATE_SNAPSHOTS=1 to
+// write new snapshots instead.
+#[cfg(test)]
+fn check_snapshot(name: &str, actual: &str) {
+    let path = format!("{}/src/snapshots/{}", env!("CARGO_MANIFEST_DIR"), name);
+    if std::env::var_os("BLAU_UPDATE_SNAPSHOTS").is_some() {
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("Missing snapshot: {}", path));
+    assert!(expected == actual, "{} doesn't match its snapshot", name);
+}
+
+#[test]
+fn drawing_a_player() {
+    let mut p = PlayerState::new("<ann>");
+    p.add_tiles(0, Color::Blue, 1).unwrap();
+    p.score_round(&RuleSet::default()).unwrap();
+    p.add_tiles(2, Color::Red, 2).unwrap();
+    p.send_to_trash(Color::Start, 1);
+    let svg = player_svg(&p, &RuleSet::default());
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.contains("&lt;ann&gt;"));
+    check_snapshot("player.svg", &svg);
+}
+
+#[test]
+fn drawing_a_game_with_a_move() {
+    use crate::agent::{Agent, GreedyAgent};
+    use rand::SeedableRng;
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(7);
+    let mut game = GameState::new(&["ann", "bob"], &mut rng).unwrap();
+    game.start_round();
+    let agent = GreedyAgent::new();
+    for _ in 0..3 {
+        let m = agent.choose_action(&game);
+        game.take_turn(&m).unwrap();
+    }
+    let m = agent.choose_action(&game);
+    let svg = game_svg(&game, Some(&m)).unwrap();
+    assert_eq!(svg.matches("<circle").count(), 5);
+    assert_eq!(svg.matches("marker-end").count(), 1);
+    check_snapshot("game.svg", &svg);
+    assert!(!game_svg(&game, None).unwrap().contains("marker-end"));
+    let bad = Move {
+        factory_idx: 9,
+        ..m
+    };
+    assert!(game_svg(&game, Some(&bad)).is_err());
+}